@@ -0,0 +1,78 @@
+//! Perft speed of the bitboard move generator against the old mailbox one.
+//! Run with `cargo bench`, both have to agree on every node count.
+
+use std::time::{Duration, Instant};
+
+use cirno::moves::legal_moves::{generate_legal_moves, generate_legal_moves_mailbox};
+use cirno::{load_valid_fen_state, precompute_squares_to_edge, ChessState, Move};
+
+type Generator<'a> = &'a dyn Fn(&ChessState) -> Vec<Move>;
+
+// (name, fen, depth), https://www.chessprogramming.org/Perft_Results
+const POSITIONS: [(&str, &str, u32); 5] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 5),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 4),
+    ("position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+    ("position 4", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 4),
+    ("position 5", "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 4),
+];
+
+fn perft(state: &mut ChessState, depth: u32, generate: Generator) -> u64 {
+    let moves = generate(state);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for mov in moves {
+        let undo = state.make_move(mov);
+        nodes += perft(state, depth - 1, generate);
+        state.unmake_move(undo);
+    }
+    nodes
+}
+
+fn time_perft(fen: &str, depth: u32, generate: Generator) -> (u64, Duration) {
+    let mut state = load_valid_fen_state(fen.to_string()).unwrap();
+    let before = Instant::now();
+    let nodes = perft(&mut state, depth, generate);
+    (nodes, before.elapsed())
+}
+
+fn nodes_per_second(nodes: u64, elapsed: Duration) -> f64 {
+    nodes as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+fn main() {
+    let sqs_to_edge = precompute_squares_to_edge();
+    // builds the magic tables before anything's timed.
+    time_perft(POSITIONS[0].1, 1, &generate_legal_moves);
+    // the old generator still walks the mailbox with the squares to edge table.
+    let mailbox = |state: &ChessState| generate_legal_moves_mailbox(state, &sqs_to_edge);
+
+    let (mut mailbox_total, mut bitboard_total) = (Duration::ZERO, Duration::ZERO);
+    for (name, fen, depth) in POSITIONS {
+        let (mailbox_nodes, mailbox_elapsed) = time_perft(fen, depth, &mailbox);
+        let (bitboard_nodes, bitboard_elapsed) = time_perft(fen, depth, &generate_legal_moves);
+        assert_eq!(mailbox_nodes, bitboard_nodes, "the generators disagree on {}", name);
+        mailbox_total += mailbox_elapsed;
+        bitboard_total += bitboard_elapsed;
+
+        println!(
+            "{:<11} depth {} {:>10} nodes   mailbox {:>9.2?} ({:>5.1} Mnps)   bitboards {:>9.2?} ({:>5.1} Mnps)   {:.1}x",
+            name,
+            depth,
+            bitboard_nodes,
+            mailbox_elapsed,
+            nodes_per_second(mailbox_nodes, mailbox_elapsed) / 1e6,
+            bitboard_elapsed,
+            nodes_per_second(bitboard_nodes, bitboard_elapsed) / 1e6,
+            mailbox_elapsed.as_secs_f64() / bitboard_elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+    println!(
+        "total: mailbox {:.2?}, bitboards {:.2?}, {:.1}x faster",
+        mailbox_total,
+        bitboard_total,
+        mailbox_total.as_secs_f64() / bitboard_total.as_secs_f64().max(f64::EPSILON)
+    );
+}
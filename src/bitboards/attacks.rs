@@ -0,0 +1,202 @@
+use crate::bitboards::bitboard::{squares, Bitboards};
+use crate::bitboards::magic::{bishop_attacks, rook_attacks};
+use crate::chess_state::ChessState;
+use crate::types::{ChessPieces, PieceColor};
+
+// (rank, file)
+const KNIGHT_OFFSETS: [(i16, i16); 8] = [(-1, -2), (-2, -1), (-2, 1), (-1, 2), (1, -2), (2, -1), (2, 1), (1, 2)];
+const KING_OFFSETS: [(i16, i16); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const WHITE_PAWN_OFFSETS: [(i16, i16); 2] = [(1, -1), (1, 1)];
+const BLACK_PAWN_OFFSETS: [(i16, i16); 2] = [(-1, -1), (-1, 1)];
+
+// worked out at compile time, like the zobrist keys.
+pub const KNIGHT_ATTACKS: [u64; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+pub const KING_ATTACKS: [u64; 64] = leaper_attacks(&KING_OFFSETS);
+// [color][square], the diagonals a pawn of that color captures on.
+pub const PAWN_ATTACKS: [[u64; 64]; 2] = [leaper_attacks(&WHITE_PAWN_OFFSETS), leaper_attacks(&BLACK_PAWN_OFFSETS)];
+
+const fn leaper_attacks(offsets: &[(i16, i16)]) -> [u64; 64] {
+    let mut attacks = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let (rank, file) = ((square / 8) as i16, (square % 8) as i16);
+        let mut index = 0;
+        while index < offsets.len() {
+            let (target_rank, target_file) = (rank + offsets[index].0, file + offsets[index].1);
+            if target_rank >= 0 && target_rank < 8 && target_file >= 0 && target_file < 8 {
+                attacks[square] |= 1 << (target_rank * 8 + target_file);
+            }
+            index += 1;
+        }
+        square += 1;
+    }
+    attacks
+}
+
+pub fn knight_attacks(square: usize) -> u64 {
+    KNIGHT_ATTACKS[square]
+}
+
+pub fn king_attacks(square: usize) -> u64 {
+    KING_ATTACKS[square]
+}
+
+/**
+ * The squares a pawn of color on square captures on, whether anything's there or not.
+ */
+pub fn pawn_attacks(color: &PieceColor, square: usize) -> u64 {
+    match color {
+        PieceColor::White => PAWN_ATTACKS[0][square],
+        PieceColor::Black => PAWN_ATTACKS[1][square],
+        PieceColor::None => 0,
+    }
+}
+
+/**
+ * The squares strictly between two squares on the same rank, file or diagonal,
+ *      0 if they don't share a line.
+ * A slider on one hitting the other from both ends leaves exactly the squares in between.
+ */
+pub fn between(from: usize, to: usize) -> u64 {
+    let (from_bit, to_bit) = (1u64 << from, 1u64 << to);
+    if rook_attacks(from, 0) & to_bit != 0 {
+        rook_attacks(from, to_bit) & rook_attacks(to, from_bit)
+    } else if bishop_attacks(from, 0) & to_bit != 0 {
+        bishop_attacks(from, to_bit) & bishop_attacks(to, from_bit)
+    } else {
+        0
+    }
+}
+
+/**
+ * Every piece of either color hitting square, mask it with a color's pieces for just theirs.
+ */
+pub fn attackers_to(state: &ChessState, square: usize) -> u64 {
+    attackers_to_with_occupancy(&state.bitboards, square, state.bitboards.occupied())
+}
+
+/**
+ * Whether any piece of by_color hits square, it doesn't matter what's standing there.
+ * Cheaper than attackers_to since it stops at the first piece type that does.
+ */
+pub fn is_square_attacked(state: &ChessState, square: usize, by_color: &PieceColor) -> bool {
+    let bitboards = &state.bitboards;
+    let their = |piece_type| bitboards.pieces(by_color, piece_type);
+    let queens = their(ChessPieces::Queens);
+    // one of their pawns hits square from where one of our pawns on square would capture.
+    let defender_color = match by_color {
+        PieceColor::White => PieceColor::Black,
+        PieceColor::Black => PieceColor::White,
+        PieceColor::None => return false,
+    };
+
+    pawn_attacks(&defender_color, square) & their(ChessPieces::Pawns) != 0
+        || knight_attacks(square) & their(ChessPieces::Knights) != 0
+        || king_attacks(square) & their(ChessPieces::Kings) != 0
+        || bishop_attacks(square, bitboards.occupied()) & (their(ChessPieces::Bishops) | queens) != 0
+        || rook_attacks(square, bitboards.occupied()) & (their(ChessPieces::Rooks) | queens) != 0
+}
+
+/**
+ * Every square color attacks as one bitboard, empty squares and its own pieces included.
+ */
+pub fn attack_map(state: &ChessState, color: &PieceColor) -> u64 {
+    attack_map_with_occupancy(&state.bitboards, color, state.bitboards.occupied())
+}
+
+/**
+ * attackers_to on a made up occupancy, like the board with a piece lifted off.
+ */
+pub fn attackers_to_with_occupancy(bitboards: &Bitboards, square: usize, occupied: u64) -> u64 {
+    let straight = bitboards.both(ChessPieces::Rooks) | bitboards.both(ChessPieces::Queens);
+    let diagonal = bitboards.both(ChessPieces::Bishops) | bitboards.both(ChessPieces::Queens);
+    (pawn_attacks(&PieceColor::Black, square) & bitboards.pieces(&PieceColor::White, ChessPieces::Pawns))
+        | (pawn_attacks(&PieceColor::White, square) & bitboards.pieces(&PieceColor::Black, ChessPieces::Pawns))
+        | (knight_attacks(square) & bitboards.both(ChessPieces::Knights))
+        | (king_attacks(square) & bitboards.both(ChessPieces::Kings))
+        | (rook_attacks(square, occupied) & straight)
+        | (bishop_attacks(square, occupied) & diagonal)
+}
+
+/**
+ * attack_map on a made up occupancy, the move generator takes the king off so it can't
+ *      step back along a checking ray.
+ */
+pub fn attack_map_with_occupancy(bitboards: &Bitboards, color: &PieceColor, occupied: u64) -> u64 {
+    let mut attacked = 0;
+    for square in squares(bitboards.pieces(color, ChessPieces::Pawns)) {
+        attacked |= pawn_attacks(color, square);
+    }
+    for square in squares(bitboards.pieces(color, ChessPieces::Knights)) {
+        attacked |= knight_attacks(square);
+    }
+    for square in squares(bitboards.pieces(color, ChessPieces::Kings)) {
+        attacked |= king_attacks(square);
+    }
+    let straight = bitboards.pieces(color, ChessPieces::Rooks) | bitboards.pieces(color, ChessPieces::Queens);
+    for square in squares(straight) {
+        attacked |= rook_attacks(square, occupied);
+    }
+    let diagonal = bitboards.pieces(color, ChessPieces::Bishops) | bitboards.pieces(color, ChessPieces::Queens);
+    for square in squares(diagonal) {
+        attacked |= bishop_attacks(square, occupied);
+    }
+    attacked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    #[test]
+    fn leapers() {
+        // a1, b1, d4
+        assert_eq!(knight_attacks(0), (1 << 10) | (1 << 17));
+        assert_eq!(knight_attacks(27).count_ones(), 8);
+        assert_eq!(king_attacks(0), (1 << 1) | (1 << 8) | (1 << 9));
+        assert_eq!(king_attacks(27).count_ones(), 8);
+        // pawns on the a and h files only capture one way.
+        assert_eq!(pawn_attacks(&PieceColor::White, 8), 1 << 17);
+        assert_eq!(pawn_attacks(&PieceColor::Black, 55), 1 << 46);
+        assert_eq!(pawn_attacks(&PieceColor::White, 12), (1 << 19) | (1 << 21));
+    }
+
+    #[test]
+    fn squares_between() {
+        // a1-h1, a1-h8, e1-e8
+        assert_eq!(between(0, 7), 0x7E);
+        assert_eq!(between(0, 63), (1 << 9) | (1 << 18) | (1 << 27) | (1 << 36) | (1 << 45) | (1 << 54));
+        assert_eq!(between(60, 4).count_ones(), 6);
+        assert_eq!(between(0, 1), 0);
+        // a1-b3 isn't a line.
+        assert_eq!(between(0, 17), 0);
+    }
+
+    #[test]
+    fn attacks_on_a_position() {
+        // white: Ke1 Rd1 Nf3 Pe4, black: Ke8 Bb4 Pd5
+        let state = load_fen_state("4k3/8/8/3p4/1b2P3/5N2/8/3RK3 w - - 0 1".to_string()).unwrap();
+        let (e1, d2, d5, e5, e6) = (4, 11, 35, 36, 44);
+
+        // the bishop hits d2 and e1, the pawn hits e4 and c4.
+        assert!(is_square_attacked(&state, e1, &PieceColor::Black));
+        assert!(is_square_attacked(&state, d2, &PieceColor::Black));
+        // pawns attack empty squares too.
+        assert!(is_square_attacked(&state, 26, &PieceColor::Black));
+        assert!(!is_square_attacked(&state, e6, &PieceColor::White));
+
+        // d5 is hit by the rook up the file and the e4 pawn, e5 by the knight only.
+        assert_eq!(attackers_to(&state, d5) & state.bitboards.color(&PieceColor::White), (1 << 3) | (1 << 28));
+        assert_eq!(attackers_to(&state, e5), 1 << 21);
+
+        let white = attack_map(&state, &PieceColor::White);
+        let black = attack_map(&state, &PieceColor::Black);
+        assert_eq!(white & 1 << d5, 1 << d5);
+        assert_eq!(black & 1 << e1, 1 << e1);
+        for square in 0..64 {
+            assert_eq!(white & 1 << square != 0, is_square_attacked(&state, square, &PieceColor::White));
+            assert_eq!(black & 1 << square != 0, is_square_attacked(&state, square, &PieceColor::Black));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::types::{BoardPiece, ChessPieces, PieceColor};
+
+pub const RANK_1: u64 = 0xFF;
+pub const RANK_8: u64 = 0xFF << 56;
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+
+/**
+ * The board as one u64 per piece type and color, a1 is the lowest bit and h8 the highest.
+ *
+ * ChessState keeps these next to the mailbox board, make_move and unmake_move update both.
+ * The mailbox is still what display and FEN read, the bitboards are for move generation.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bitboards {
+    // [color][piece], in the order of PieceColor and ChessPieces.
+    pub pieces: [[u64; 6]; 2],
+    pub colors: [u64; 2],
+}
+
+impl Bitboards {
+    pub fn from_board(board: &[BoardPiece; 64]) -> Bitboards {
+        let mut bitboards = Bitboards::default();
+        for (square, piece) in board.iter().enumerate() {
+            bitboards.toggle(square, *piece);
+        }
+        bitboards
+    }
+
+    /**
+     * Puts the piece on the square, or takes it off if it's already there.
+     * Empty squares are ignored, so a capture can toggle whatever was on the target.
+     */
+    pub fn toggle(&mut self, square: usize, piece: BoardPiece) {
+        if let (Some(color), Some(piece_index)) = (color_index(&piece.piece_color), piece_index(piece.piece_type)) {
+            self.pieces[color][piece_index] ^= 1 << square;
+            self.colors[color] ^= 1 << square;
+        }
+    }
+
+    pub fn pieces(&self, color: &PieceColor, piece_type: ChessPieces) -> u64 {
+        match (color_index(color), piece_index(piece_type)) {
+            (Some(color), Some(piece_index)) => self.pieces[color][piece_index],
+            _ => 0,
+        }
+    }
+
+    /**
+     * Both colors' pieces of a type, like every rook on the board.
+     */
+    pub fn both(&self, piece_type: ChessPieces) -> u64 {
+        self.pieces(&PieceColor::White, piece_type) | self.pieces(&PieceColor::Black, piece_type)
+    }
+
+    pub fn color(&self, color: &PieceColor) -> u64 {
+        color_index(color).map_or(0, |color| self.colors[color])
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+}
+
+pub fn color_index(color: &PieceColor) -> Option<usize> {
+    match color {
+        PieceColor::White => Some(0),
+        PieceColor::Black => Some(1),
+        PieceColor::None => None,
+    }
+}
+
+fn piece_index(piece_type: ChessPieces) -> Option<usize> {
+    match piece_type {
+        ChessPieces::Empty => None,
+        piece_type => Some(piece_type as usize),
+    }
+}
+
+/**
+ * The squares set in a bitboard, lowest first.
+ */
+pub fn squares(bitboard: u64) -> Squares {
+    Squares(bitboard)
+}
+
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        // clears the lowest bit.
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
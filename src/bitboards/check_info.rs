@@ -0,0 +1,155 @@
+use crate::bitboards::attacks::{attackers_to_with_occupancy, between};
+use crate::bitboards::bitboard::{squares, Bitboards};
+use crate::bitboards::magic::{bishop_attacks, rook_attacks};
+use crate::helpers::color::switch_color;
+use crate::types::{ChessPieces, PieceColor};
+
+/**
+ * A piece that can't leave the line between its king and the enemy slider behind it.
+ * ray is everywhere it can still go: the squares between the king and the pinner, plus the pinner.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pin {
+    pub square: usize,
+    pub pinner: usize,
+    pub ray: u64,
+}
+
+/**
+ * What's hitting the side to move's king and what's tied to it, worked out once per position.
+ * ChessState keeps one up to date through make_move, unmake_move and rebuild_bitboards.
+ *
+ * Discovered check candidates are the side to move's own pieces standing alone between
+ *      one of its sliders and the enemy king, moving one off that line gives check.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CheckInfo {
+    // enemy pieces attacking the king of the side to move.
+    pub checkers: u64,
+    // every pinned square, the rays are in pins.
+    pub pinned: u64,
+    pub discovered_check_candidates: u64,
+    // there's at most one pin per direction out of the king.
+    pins: [Pin; 8],
+    pin_count: usize,
+}
+
+impl CheckInfo {
+    pub fn new(bitboards: &Bitboards, color_to_move: &PieceColor) -> CheckInfo {
+        let mut info = CheckInfo::default();
+        if *color_to_move == PieceColor::None {
+            return info;
+        }
+        let them = switch_color(color_to_move);
+        let ours = bitboards.color(color_to_move);
+        let theirs = bitboards.color(&them);
+        let occupied = ours | theirs;
+
+        let kings = bitboards.pieces(color_to_move, ChessPieces::Kings);
+        if kings != 0 {
+            let king_square = kings.trailing_zeros() as usize;
+            info.checkers = attackers_to_with_occupancy(bitboards, king_square, occupied) & theirs;
+            // their sliders lined up with the king, looking straight through our pieces.
+            for pinner in squares(line_up(bitboards, &them, king_square, theirs)) {
+                let ray = between(king_square, pinner);
+                let blockers = ray & occupied;
+                if blockers.count_ones() == 1 && blockers & ours != 0 {
+                    let square = blockers.trailing_zeros() as usize;
+                    info.pinned |= blockers;
+                    info.pins[info.pin_count] = Pin { square, pinner, ray: ray | 1 << pinner };
+                    info.pin_count += 1;
+                }
+            }
+        }
+
+        let enemy_kings = bitboards.pieces(&them, ChessPieces::Kings);
+        if enemy_kings != 0 {
+            let enemy_king_square = enemy_kings.trailing_zeros() as usize;
+            // the same the other way round, our sliders with one of our own pieces in front.
+            for slider in squares(line_up(bitboards, color_to_move, enemy_king_square, theirs)) {
+                let blockers = between(enemy_king_square, slider) & occupied;
+                if blockers.count_ones() == 1 && blockers & ours != 0 {
+                    info.discovered_check_candidates |= blockers;
+                }
+            }
+        }
+        info
+    }
+
+    pub fn is_check(&self) -> bool {
+        self.checkers != 0
+    }
+
+    /**
+     * Two pieces giving check at once, nothing can block or take both so only the king can move.
+     */
+    pub fn is_double_check(&self) -> bool {
+        self.checkers.count_ones() > 1
+    }
+
+    pub fn pins(&self) -> &[Pin] {
+        &self.pins[..self.pin_count]
+    }
+
+    /**
+     * The squares the piece on square can move to without exposing its king, everything if it isn't pinned.
+     */
+    pub fn pin_ray(&self, square: usize) -> u64 {
+        if self.pinned & 1 << square == 0 {
+            return u64::MAX;
+        }
+        self.pins().iter().find(|pin| pin.square == square).map_or(u64::MAX, |pin| pin.ray)
+    }
+}
+
+// color's rooks, bishops and queens that could hit square on the given occupancy.
+fn line_up(bitboards: &Bitboards, color: &PieceColor, square: usize, occupied: u64) -> u64 {
+    let queens = bitboards.pieces(color, ChessPieces::Queens);
+    let straight = bitboards.pieces(color, ChessPieces::Rooks) | queens;
+    let diagonal = bitboards.pieces(color, ChessPieces::Bishops) | queens;
+    (rook_attacks(square, occupied) & straight) | (bishop_attacks(square, occupied) & diagonal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    #[test]
+    fn checks_pins_and_discoveries() {
+        // white: Ke1 Bd2 Nf3 Re4, black: Ke8 Qe7 Bb4 Rh3, the bishop and rook are both pinned.
+        let state = load_fen_state("4k3/4q3/8/8/1b2R3/5N1r/3B4/4K3 w - - 0 1".to_string()).unwrap();
+        let info = state.check_info;
+        let (d2, e4, b4, e7) = (11, 28, 25, 52);
+        assert!(!info.is_check());
+        assert_eq!(info.pinned, 1 << d2 | 1 << e4);
+        assert_eq!(info.pin_ray(d2), 1 << d2 | 1 << 18 | 1 << b4);
+        assert_eq!(info.pin_ray(e4), between(4, e7) | 1 << e7);
+        assert_eq!(info.pin_ray(21), u64::MAX);
+        assert_eq!(info.pins().len(), 2);
+
+        // black to move: the e7 queen is between the white rook and the king.
+        let black = CheckInfo::new(&state.bitboards, &PieceColor::Black);
+        assert_eq!(black.pinned, 1 << e7);
+        assert_eq!(black.pin_ray(e7), between(60, e4) | 1 << e4);
+
+        // white's knight steps off the bishop's diagonal to the black king with check.
+        let discovery = load_fen_state("4k3/8/8/8/8/1N6/8/B3K3 w - - 0 1".to_string()).unwrap();
+        assert_eq!(discovery.check_info.discovered_check_candidates, 0);
+        let discovery = load_fen_state("7k/8/8/8/8/2N5/8/B3K3 w - - 0 1".to_string()).unwrap();
+        assert_eq!(discovery.check_info.discovered_check_candidates, 1 << 18);
+    }
+
+    #[test]
+    fn single_and_double_checks() {
+        let single = load_fen_state("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1".to_string()).unwrap();
+        assert!(single.check_info.is_check());
+        assert!(!single.check_info.is_double_check());
+        assert_eq!(single.check_info.checkers, 1 << 12);
+
+        // rook on the file and knight on d3.
+        let double = load_fen_state("4k3/8/8/8/4r3/3n4/8/4K3 w - - 0 1".to_string()).unwrap();
+        assert!(double.check_info.is_double_check());
+        assert_eq!(double.check_info.checkers, 1 << 28 | 1 << 19);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod attacks;
+pub mod bitboard;
+pub mod check_info;
+pub mod magic;
@@ -1,111 +1,395 @@
 use crate::{
+    bitboards::bitboard::Bitboards,
+    bitboards::check_info::CheckInfo,
+    helpers::color::switch_color,
+    helpers::zobrist::{castle_key, compute_zobrist_key, en_passant_key, piece_key, side_key},
+    moves::legal_moves::generate_legal_moves,
     types::{ChessPieces, MoveType},
     BoardPiece, Castle, Move, PieceColor,
 };
 
-#[derive(Debug, Clone)]
+pub const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/**
+ * A whole position, everything a FEN string holds plus what the engine tracks on top.
+ * Load one with load_valid_fen_state and move it along with make_move and unmake_move.
+ *
+ * board and bitboards hold the same pieces, anything editing board directly has to
+ *      call rebuild_bitboards afterwards, which also brings check_info back up to date.
+ */
+#[derive(Debug, Clone, PartialEq)]
 pub struct ChessState {
     pub board: [BoardPiece; 64],
+    // the same pieces as bitboards, for move generation.
+    pub bitboards: Bitboards,
     pub color_to_move: PieceColor,
     pub is_able_to_castle: Castle,
-    pub is_check: bool,
-    pub pinned_pieces: Vec<BoardPiece>,
+    // the square a pawn skipped over with a double push, if any.
+    pub en_passant: Option<i16>,
+    // halfmoves since the last capture or pawn move.
+    pub halfmove_clock: u32,
+    // starts at 1 and goes up after black moves.
+    pub fullmove_number: u32,
+    // identifies the position, kept up to date by make_move and unmake_move.
+    pub zobrist_key: u64,
+    // keys of every position before this one, oldest first, for spotting repetitions.
+    pub position_history: Vec<u64>,
+    // checkers, pins and discovered check candidates for the side to move.
+    pub check_info: CheckInfo,
 }
 
 /**
- * Castling no work yet!
- * Castling checks no work yet!
- *
+ * Everything make_move overwrites, so unmake_move can put the position back exactly.
  */
-pub fn make_move(
-    board: &mut [BoardPiece; 64],
-    friendly_movements: &Vec<Move>,
+#[derive(Debug, Clone)]
+pub struct UndoInfo {
+    pub mov: Move,
+    pub moved_piece: BoardPiece,
+    // for en passant this is the pawn beside the target square.
+    pub captured_piece: BoardPiece,
+    pub is_able_to_castle: Castle,
+    pub en_passant: Option<i16>,
+    pub halfmove_clock: u32,
+    pub zobrist_key: u64,
+}
+
+impl ChessState {
+    /**
+     * Plays a move and hands the turn to the other side.
+     * The move should come from the move generator, nothing is validated here.
+     *
+     * Castling drags the rook along, a king move drops both its castling rights and
+     *      anything moving from or onto a castling rook's square drops the right tied to it.
+     */
+    pub fn make_move(&mut self, mov: Move) -> UndoInfo {
+        let start_piece = self.board[mov.start_square as usize];
+        let castle_rook = match mov.move_type {
+            MoveType::Castle => Some(castle_rook_squares(&mov, &self.is_able_to_castle)),
+            _ => None,
+        };
+        // in Chess960 the king can land on its own rook, that's not a capture.
+        let mut end_piece = match castle_rook {
+            Some(_) => BoardPiece { ..Default::default() },
+            None => self.board[mov.target_square as usize],
+        };
+        let rook = BoardPiece {
+            piece_type: ChessPieces::Rooks,
+            piece_color: start_piece.piece_color,
+        };
+
+        let undo = UndoInfo {
+            mov,
+            moved_piece: start_piece,
+            captured_piece: end_piece,
+            is_able_to_castle: self.is_able_to_castle.clone(),
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+        };
+
+        // everything that's about to change gets XOR'd out first, then back in once it's changed.
+        let mut zobrist_key = self.zobrist_key
+            ^ castle_key(&self.is_able_to_castle)
+            ^ en_passant_key(self)
+            ^ side_key(&self.color_to_move);
+        let board = &mut self.board;
+        let bitboards = &mut self.bitboards;
+
+        // the king moves below like any other piece, the rook is lifted off first and put
+        //      down after, since either of them can land where the other started.
+        if let Some((rook_start_square, _)) = castle_rook {
+            zobrist_key ^= piece_key(rook, rook_start_square);
+            bitboards.toggle(rook_start_square, rook);
+            board[rook_start_square] = BoardPiece { ..Default::default() };
+        }
+        // the captured pawn isn't on the target square, it's beside the moving pawn.
+        if mov.move_type == MoveType::EnPassant {
+            let captured_square = en_passant_capture_square(&mov);
+            end_piece = board[captured_square];
+            zobrist_key ^= piece_key(end_piece, captured_square);
+            bitboards.toggle(captured_square, end_piece);
+            board[captured_square] = BoardPiece { ..Default::default() };
+        } else {
+            zobrist_key ^= piece_key(end_piece, mov.target_square as usize);
+            bitboards.toggle(mov.target_square as usize, end_piece);
+        }
+
+        let landing_piece = match mov.promotion {
+            Some(piece_type) => BoardPiece {
+                piece_type,
+                piece_color: start_piece.piece_color,
+            },
+            None => start_piece,
+        };
+        zobrist_key ^= piece_key(start_piece, mov.start_square as usize)
+            ^ piece_key(landing_piece, mov.target_square as usize);
+        bitboards.toggle(mov.start_square as usize, start_piece);
+        bitboards.toggle(mov.target_square as usize, landing_piece);
+        board[mov.start_square as usize] = BoardPiece {
+            ..Default::default() // Empty.
+        };
+        board[mov.target_square as usize] = landing_piece;
+        if let Some((_, rook_target_square)) = castle_rook {
+            zobrist_key ^= piece_key(rook, rook_target_square);
+            bitboards.toggle(rook_target_square, rook);
+            board[rook_target_square] = rook;
+        }
+
+        if start_piece.piece_type == ChessPieces::Kings {
+            self.is_able_to_castle.clear(&start_piece.piece_color);
+        }
+        self.is_able_to_castle.remove_rook(mov.start_square as usize);
+        self.is_able_to_castle.remove_rook(mov.target_square as usize);
+
+        // a double pawn push leaves the skipped square open for en passant.
+        self.en_passant = if start_piece.piece_type == ChessPieces::Pawns
+            && (mov.target_square - mov.start_square).abs() == 16
+        {
+            Some((mov.start_square + mov.target_square) / 2)
+        } else {
+            None
+        };
+
+        if start_piece.piece_type == ChessPieces::Pawns
+            || end_piece.piece_type != ChessPieces::Empty
+        {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.color_to_move == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.color_to_move = switch_color(&self.color_to_move);
+
+        self.position_history.push(self.zobrist_key);
+        self.zobrist_key = zobrist_key
+            ^ castle_key(&self.is_able_to_castle)
+            ^ en_passant_key(self)
+            ^ side_key(&self.color_to_move);
+        debug_assert_eq!(
+            self.zobrist_key,
+            compute_zobrist_key(self),
+            "zobrist key drifted after {:?}",
+            mov
+        );
+        debug_assert_eq!(self.bitboards, Bitboards::from_board(&self.board), "bitboards drifted after {:?}", mov);
+        self.check_info = CheckInfo::new(&self.bitboards, &self.color_to_move);
+
+        UndoInfo {
+            captured_piece: end_piece,
+            ..undo
+        }
+    }
+
+    /**
+     * Takes back the move make_move returned the UndoInfo for.
+     * Moves have to be unmade in the reverse order they were made.
+     */
+    pub fn unmake_move(&mut self, undo: UndoInfo) {
+        let UndoInfo {
+            mov,
+            moved_piece,
+            captured_piece,
+            is_able_to_castle,
+            en_passant,
+            halfmove_clock,
+            zobrist_key,
+        } = undo;
+        let board = &mut self.board;
+        let bitboards = &mut self.bitboards;
+
+        if mov.move_type == MoveType::Castle {
+            // both come off before either goes back, like in make_move.
+            let (rook_start_square, rook_target_square) = castle_rook_squares(&mov, &is_able_to_castle);
+            let rook = board[rook_target_square];
+            bitboards.toggle(mov.target_square as usize, moved_piece);
+            bitboards.toggle(rook_target_square, rook);
+            board[mov.target_square as usize] = BoardPiece { ..Default::default() };
+            board[rook_target_square] = BoardPiece { ..Default::default() };
+            bitboards.toggle(mov.start_square as usize, moved_piece);
+            bitboards.toggle(rook_start_square, rook);
+            board[mov.start_square as usize] = moved_piece;
+            board[rook_start_square] = rook;
+        } else {
+            // the piece that landed, which isn't the one that left if it promoted.
+            bitboards.toggle(mov.target_square as usize, board[mov.target_square as usize]);
+            bitboards.toggle(mov.start_square as usize, moved_piece);
+            board[mov.start_square as usize] = moved_piece;
+            if mov.move_type == MoveType::EnPassant {
+                bitboards.toggle(en_passant_capture_square(&mov), captured_piece);
+                board[mov.target_square as usize] = BoardPiece { ..Default::default() };
+                board[en_passant_capture_square(&mov)] = captured_piece;
+            } else {
+                bitboards.toggle(mov.target_square as usize, captured_piece);
+                board[mov.target_square as usize] = captured_piece;
+            }
+        }
+
+        self.color_to_move = switch_color(&self.color_to_move);
+        if self.color_to_move == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+        self.is_able_to_castle = is_able_to_castle;
+        self.en_passant = en_passant;
+        self.halfmove_clock = halfmove_clock;
+        self.zobrist_key = zobrist_key;
+        self.position_history.pop();
+        debug_assert_eq!(
+            self.zobrist_key,
+            compute_zobrist_key(self),
+            "zobrist key drifted undoing {:?}",
+            mov
+        );
+        debug_assert_eq!(self.bitboards, Bitboards::from_board(&self.board), "bitboards drifted undoing {:?}", mov);
+        self.check_info = CheckInfo::new(&self.bitboards, &self.color_to_move);
+    }
+
+    /**
+     * Brings the bitboards back in line with board after it's been edited by hand,
+     *      along with check_info, so call it after changing color_to_move by hand too.
+     */
+    pub fn rebuild_bitboards(&mut self) {
+        self.bitboards = Bitboards::from_board(&self.board);
+        self.check_info = CheckInfo::new(&self.bitboards, &self.color_to_move);
+    }
+}
+
+/**
+ * Finds the move the user asked for out of the friendly movements, if it's in there.
+ */
+pub fn find_move(
+    friendly_movements: &[Move],
     start_square_index: u32,
     end_square_index: u32,
-) -> Result<(Move, BoardPiece, BoardPiece), &'static str> {
+    promotion: Option<ChessPieces>,
+) -> Result<Move, &'static str> {
     let moves = friendly_movements.iter().find(|moves| {
-        (moves.start_square as u32, moves.target_square as u32)
-            == (start_square_index, end_square_index)
+        (moves.start_square as u32, moves.target_square as u32, moves.promotion)
+            == (start_square_index, end_square_index, promotion)
     });
 
     match moves {
-        Some(moves) => {
-            let start_piece = board[start_square_index as usize];
-            let end_piece = board[end_square_index as usize];
-
-            if moves.move_type == MoveType::NoCapture && end_piece.piece_type != ChessPieces::Empty
-            {
-                return Err("Move not allowed due to NoCapture pawn behaviour.");
-            }
-            // do unmake move later dear fucking god.
-            if moves.move_type == MoveType::Castle {
-                match start_piece.piece_color {
-                    PieceColor::White => {
-                        if end_square_index == 2 { // queenside
-                            board[2] = start_piece.clone();
-                            board[3] = board[0];
-                            board[2] = BoardPiece { ..Default::default() };
-                            board[0] = BoardPiece { ..Default::default() };
-                        }
-                        if end_square_index == 6 { // kingside
-                            board[6] = start_piece.clone();
-                            board[5] = board[7];
-                            board[6] = BoardPiece { ..Default::default() };
-                            board[7] = BoardPiece { ..Default::default() };
-                        }
-                    },
-                    PieceColor::Black => {
-                        // assuming rook positions to figure out queenside and kingside.
-                        if end_square_index == 58 { // queenside
-                            board[58] = start_piece.clone();
-                            board[59] = board[56].clone();
-                            board[58] = BoardPiece { ..Default::default() };
-                            board[56] = BoardPiece { ..Default::default() };
-                        }
-                        if end_square_index == 62 { // kingside
-                            board[62] = start_piece.clone();
-                            board[61] = board[63].clone();
-                            board[62] = BoardPiece { ..Default::default() };
-                            board[63] = BoardPiece { ..Default::default() };
-                        }
-                    },
-                    PieceColor::None => {}
-                }
-            }
-
-            board[end_square_index as usize] = start_piece.clone();
-            board[start_square_index as usize] = BoardPiece {
-                ..Default::default() // Empty.
-            };
-            Ok((moves.clone(), start_piece.clone(), end_piece.clone()))
+        Some(moves) => Ok(*moves),
+        None if promotion.is_none()
+            && friendly_movements.iter().any(|moves| {
+                (moves.start_square as u32, moves.target_square as u32)
+                    == (start_square_index, end_square_index)
+            }) =>
+        {
+            Err("Promotions need a piece, e.g. e7e8q.")
         }
         None => Err("Move not allowed."),
     }
 }
 
-pub fn unmake_move(
-    board: &mut [BoardPiece; 64],
-    piece_and_move: (Move, BoardPiece, BoardPiece),
-) -> Result<(), &'static str> {
-    let (move_, starting_piece, eaten_piece) = piece_and_move;
+/**
+ * Decodes coordinate notation and looks it up in the legal moves, for the engine protocols.
+ */
+pub fn parse_move(
+    state: &ChessState,
+    notation: &str,
+) -> Result<Move, &'static str> {
+    let (start_square_index, end_square_index, promotion) = algebraic_notation_decoder(notation)?;
+    find_move(
+        &generate_legal_moves(state),
+        start_square_index,
+        end_square_index,
+        promotion,
+    )
+}
+
+/**
+ * parse_move for Chess960, where castling has to be written as the king taking its own rook.
+ * Anything else would be ambiguous: the king might not move at all, or it could also just
+ *      step to the square it lands on.
+ */
+pub fn parse_chess960_move(
+    state: &ChessState,
+    notation: &str,
+) -> Result<Move, &'static str> {
+    let (start_square_index, end_square_index, promotion) = algebraic_notation_decoder(notation)?;
+    let (mover, target) = (state.board[start_square_index as usize], state.board[end_square_index as usize]);
+    let (castles, moves): (Vec<Move>, Vec<Move>) = generate_legal_moves(state)
+        .into_iter()
+        .partition(|mov| mov.move_type == MoveType::Castle);
 
-    if move_.start_square >= 64 || move_.target_square >= 64 {
-        Err("Out of bounds!")
-    } else {
-        board[move_.start_square as usize] = starting_piece;
-        board[move_.target_square as usize] = eaten_piece;
-        Ok(())
+    if mover.piece_type == ChessPieces::Kings
+        && target.piece_type == ChessPieces::Rooks
+        && target.piece_color == mover.piece_color
+    {
+        return castles
+            .into_iter()
+            .find(|mov| castle_rook_squares(mov, &state.is_able_to_castle).0 == end_square_index as usize)
+            .ok_or("Move not allowed.");
     }
+    find_move(&moves, start_square_index, end_square_index, promotion)
+}
+
+/**
+ * Where the rook starts and lands when castling, castle being the rights from before the move.
+ * The king always lands on the g or c file, so that's what tells the two sides apart,
+ *      in Chess960 the king might not move at all.
+ */
+pub fn castle_rook_squares(mov: &Move, castle: &Castle) -> (usize, usize) {
+    let first_square_of_rank = ((mov.start_square / 8) * 8) as usize;
+    let color = match first_square_of_rank {
+        0 => PieceColor::White,
+        _ => PieceColor::Black,
+    };
+    let is_kingside = is_kingside_castle(mov);
+    let (default_file, rook_target_file) = if is_kingside { (7, 5) } else { (0, 3) };
+    let rook_file = castle.rook_file(&color, is_kingside).unwrap_or(default_file);
+    (first_square_of_rank + rook_file, first_square_of_rank + rook_target_file)
+}
+
+/**
+ * Whether a castling move goes towards the h file.
+ */
+pub fn is_kingside_castle(mov: &Move) -> bool {
+    mov.target_square % 8 == 6
+}
+
+/**
+ * The square of the pawn taken by an en passant move.
+ * It's on the starting rank of the capturing pawn and the file of the target square.
+ */
+pub fn en_passant_capture_square(mov: &Move) -> usize {
+    ((mov.start_square / 8) * 8 + mov.target_square % 8) as usize
 }
 
-pub fn algebraic_notation_decoder(notation: &str) -> Result<(u32, u32), &'static str> {
+/**
+ * Decodes coordinate notation like e2e4, with an optional fifth character
+ * for the promotion piece like e7e8q.
+ */
+pub fn algebraic_notation_decoder(
+    notation: &str,
+) -> Result<(u32, u32, Option<ChessPieces>), &'static str> {
     if notation.is_empty() {
         return Err("Empty notation!");
     }
+    if !notation.is_ascii() || !matches!(notation.len(), 4 | 5) {
+        return Err("Invalid notation!");
+    }
     let (notation_start, notation_end) = notation.split_at(2);
+    let (notation_end, notation_promotion) = notation_end.split_at(2);
     let start_square_index = convert_algebraic_snippet(notation_start)?;
     let end_square_index = convert_algebraic_snippet(notation_end)?;
-    Ok((start_square_index, end_square_index))
+    let promotion = match notation_promotion.chars().next() {
+        Some(ch) => Some(convert_promotion_piece(ch)?),
+        None => None,
+    };
+    Ok((start_square_index, end_square_index, promotion))
+}
+
+pub fn convert_promotion_piece(ch: char) -> Result<ChessPieces, &'static str> {
+    match ch {
+        'Q' | 'q' => Ok(ChessPieces::Queens),
+        'R' | 'r' => Ok(ChessPieces::Rooks),
+        'B' | 'b' => Ok(ChessPieces::Bishops),
+        'N' | 'n' => Ok(ChessPieces::Knights),
+        _ => Err("Invalid promotion piece!"),
+    }
 }
 
 pub fn convert_algebraic_snippet(notation: &str) -> Result<u32, &'static str> {
@@ -122,6 +406,48 @@ pub fn convert_algebraic_snippet(notation: &str) -> Result<u32, &'static str> {
     Ok(rank * 8 + file)
 }
 
+/**
+ * The reverse of algebraic_notation_decoder, writes moves like e2e4 or e7e8q.
+ */
+pub fn algebraic_notation_encoder(mov: &Move) -> String {
+    let promotion = match mov.promotion {
+        Some(ChessPieces::Queens) => "q",
+        Some(ChessPieces::Rooks) => "r",
+        Some(ChessPieces::Bishops) => "b",
+        Some(ChessPieces::Knights) => "n",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        square_to_algebraic(mov.start_square),
+        square_to_algebraic(mov.target_square),
+        promotion
+    )
+}
+
+/**
+ * algebraic_notation_encoder for Chess960, castling comes out as the king taking its own rook (e1h1).
+ * state is the position before the move.
+ */
+pub fn chess960_notation_encoder(state: &ChessState, mov: &Move) -> String {
+    match mov.move_type {
+        MoveType::Castle => {
+            let (rook_square, _) = castle_rook_squares(mov, &state.is_able_to_castle);
+            format!("{}{}", square_to_algebraic(mov.start_square), square_to_algebraic(rook_square as i16))
+        }
+        _ => algebraic_notation_encoder(mov),
+    }
+}
+
+/**
+ * The reverse of convert_algebraic_snippet, 0 => "a1", 63 => "h8".
+ */
+pub fn square_to_algebraic(square: i16) -> String {
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = (b'1' + (square / 8) as u8) as char;
+    format!("{}{}", file, rank)
+}
+
 fn map_char_to_number(c: char) -> Option<u32> {
     match c {
         'A' | 'a' => Some(1),
@@ -138,14 +464,86 @@ fn map_char_to_number(c: char) -> Option<u32> {
 
 impl Default for ChessState {
     fn default() -> ChessState {
-        ChessState {
+        let mut state = ChessState {
             board: [BoardPiece {
                 ..Default::default()
             }; 64],
+            bitboards: Bitboards::default(),
             color_to_move: PieceColor::Black,
             is_able_to_castle: Default::default(),
-            pinned_pieces: vec![],
-            is_check: false,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist_key: 0,
+            position_history: vec![],
+            check_info: CheckInfo::default(),
+        };
+        state.zobrist_key = compute_zobrist_key(&state);
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+    use crate::moves::legal_moves::generate_legal_moves;
+
+    const POSITIONS: [&str; 8] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        // Chess960, castling kingside swaps the king and rook.
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+    ];
+
+    /**
+     * Makes and unmakes every legal move of every position in the tree down to depth,
+     *      checking the position comes back untouched each time.
+     */
+    fn assert_make_unmake_restores(
+        state: &mut ChessState,
+        depth: u32,
+    ) -> usize {
+        let mut checked = 0;
+        for mov in generate_legal_moves(state) {
+            let before = state.clone();
+            let undo = state.make_move(mov);
+            if depth > 1 {
+                checked += assert_make_unmake_restores(state, depth - 1);
+            }
+            state.unmake_move(undo);
+            assert_eq!(*state, before, "make/unmake of {:?} changed the position", mov);
+            checked += 1;
         }
+        checked
+    }
+
+    #[test]
+    fn make_then_unmake_is_identity() {
+        let mut checked = 0;
+        for fen in POSITIONS {
+            let mut state = load_fen_state(fen.to_string()).unwrap();
+            checked += assert_make_unmake_restores(&mut state, 3);
+        }
+        assert!(checked > 100_000, "only {} moves were checked", checked);
+    }
+
+    #[test]
+    fn make_move_updates_clocks_and_turn() {
+        let mut state = load_fen_state(POSITIONS[0].to_string()).unwrap();
+        let knight = find_move(&generate_legal_moves(&state), 6, 21, None).unwrap();
+        state.make_move(knight);
+        assert_eq!(state.color_to_move, PieceColor::Black);
+        assert_eq!((state.halfmove_clock, state.fullmove_number), (1, 1));
+
+        let pawn = find_move(&generate_legal_moves(&state), 52, 36, None).unwrap();
+        state.make_move(pawn);
+        assert_eq!(state.en_passant, Some(44));
+        assert_eq!((state.halfmove_clock, state.fullmove_number), (0, 2));
     }
 }
@@ -1,6 +1,9 @@
 use core::fmt;
 use std::error::Error;
 
+use crate::chess_state::square_to_algebraic;
+use crate::types::PieceColor;
+
 #[derive(Debug)]
 pub enum GameError {
     UserMoveError(String),
@@ -21,3 +24,260 @@ impl fmt::Display for GameError {
 }
 
 impl Error for GameError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FenField {
+    Board,
+    Turn,
+    Castling,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FenField::Board => "board",
+            FenField::Turn => "turn",
+            FenField::Castling => "castling",
+            FenField::EnPassant => "en passant",
+            FenField::HalfmoveClock => "halfmove clock",
+            FenField::FullmoveNumber => "fullmove number",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ * Everything that can go wrong reading a FEN string.
+ * Offsets count characters from the start of the whole FEN string.
+ *
+ * The first group is syntax, the second group only comes out of validate_fen_state.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    MissingField { field: FenField },
+    TooManyFields { offset: usize },
+    InvalidCharacter { field: FenField, offset: usize, character: char },
+    // more than 8 squares in a rank.
+    RankTooLong { offset: usize },
+    // fewer than 8 squares in a rank.
+    RankTooShort { offset: usize },
+    WrongRankCount { offset: usize, ranks: usize },
+    InvalidNumber { field: FenField, offset: usize },
+    FieldTooShort { field: FenField, offset: usize },
+
+    WrongKingCount { color: PieceColor, count: usize },
+    PawnOnBackRank { square: i16 },
+    SideNotToMoveInCheck,
+    // the right given in FEN, like 'K', without a king and rook on their home squares.
+    InconsistentCastling { right: char },
+    ImplausibleEnPassant { square: i16 },
+}
+
+impl FenError {
+    /**
+     * The FEN field at fault, None for whole-position problems.
+     */
+    pub fn field(&self) -> Option<FenField> {
+        match self {
+            FenError::MissingField { field }
+            | FenError::InvalidCharacter { field, .. }
+            | FenError::InvalidNumber { field, .. }
+            | FenError::FieldTooShort { field, .. } => Some(*field),
+            FenError::RankTooLong { .. }
+            | FenError::RankTooShort { .. }
+            | FenError::WrongRankCount { .. }
+            | FenError::WrongKingCount { .. }
+            | FenError::PawnOnBackRank { .. } => Some(FenField::Board),
+            FenError::InconsistentCastling { .. } => Some(FenField::Castling),
+            FenError::ImplausibleEnPassant { .. } => Some(FenField::EnPassant),
+            FenError::TooManyFields { .. } | FenError::SideNotToMoveInCheck => None,
+        }
+    }
+
+    /**
+     * The character offset at fault, None for errors about the position as a whole.
+     */
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            FenError::TooManyFields { offset }
+            | FenError::InvalidCharacter { offset, .. }
+            | FenError::RankTooLong { offset }
+            | FenError::RankTooShort { offset }
+            | FenError::WrongRankCount { offset, .. }
+            | FenError::InvalidNumber { offset, .. }
+            | FenError::FieldTooShort { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::MissingField { field } => write!(f, "Missing the {} field", field),
+            FenError::TooManyFields { offset } => {
+                write!(f, "Too many fields, the extra one starts at {}", offset)
+            }
+            FenError::InvalidCharacter { field, offset, character } => write!(
+                f,
+                "Invalid character '{}' in the {} field at {}",
+                character, field, offset
+            ),
+            FenError::RankTooLong { offset } => write!(f, "Rank has more than 8 squares at {}", offset),
+            FenError::RankTooShort { offset } => write!(f, "Rank has fewer than 8 squares at {}", offset),
+            FenError::WrongRankCount { offset, ranks } => {
+                write!(f, "Board has {} ranks instead of 8 at {}", ranks, offset)
+            }
+            FenError::InvalidNumber { field, offset } => {
+                write!(f, "Invalid number in the {} field at {}", field, offset)
+            }
+            FenError::FieldTooShort { field, offset } => {
+                write!(f, "The {} field ends too early at {}", field, offset)
+            }
+            FenError::WrongKingCount { color, count } => {
+                write!(f, "{:?} has {} kings instead of 1", color, count)
+            }
+            FenError::PawnOnBackRank { square } => {
+                write!(f, "Pawn on the back rank at {}", square_to_algebraic(*square))
+            }
+            FenError::SideNotToMoveInCheck => write!(f, "The side not to move is in check"),
+            FenError::InconsistentCastling { right } => {
+                write!(f, "Castling right '{}' without its king and rook on the home rank", right)
+            }
+            FenError::ImplausibleEnPassant { square } => {
+                write!(
+                    f,
+                    "No double pawn push could have left en passant square {}",
+                    square_to_algebraic(*square)
+                )
+            }
+        }
+    }
+}
+
+impl Error for FenError {}
+
+/**
+ * Why a SAN move like Nbd7 couldn't be turned into a move, each holds the SAN as given.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum SanError {
+    // not shaped like SAN at all.
+    InvalidSyntax(String),
+    // well formed, but no legal move fits.
+    IllegalMove(String),
+    // more than one legal move fits, like Nd7 with knights on b8 and f6.
+    AmbiguousMove(String),
+    // a pawn reaching the last rank without saying what it becomes.
+    MissingPromotion(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::InvalidSyntax(san) => write!(f, "{} isn't a move in SAN.", san),
+            SanError::IllegalMove(san) => write!(f, "{} isn't a legal move.", san),
+            SanError::AmbiguousMove(san) => {
+                write!(f, "{} could be more than one move, add the file or rank it comes from.", san)
+            }
+            SanError::MissingPromotion(san) => {
+                write!(f, "{} needs a promotion piece, e.g. e8=Q.", san)
+            }
+        }
+    }
+}
+
+impl Error for SanError {}
+
+/**
+ * Everything that can go wrong reading a PGN game, lines count from 1 at the start of the file.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    // reading the file itself failed.
+    Io(String),
+    InvalidTag { line: usize },
+    UnterminatedComment { line: usize },
+    // a ')' without a '(' or the other way round.
+    UnbalancedVariation { line: usize },
+    // a variation with no move before it to replace.
+    MisplacedVariation { line: usize },
+    UnexpectedToken { line: usize, token: String },
+    InvalidFen { line: usize, error: FenError },
+    InvalidMove { line: usize, error: SanError },
+}
+
+impl PgnError {
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            PgnError::Io(_) => None,
+            PgnError::InvalidTag { line }
+            | PgnError::UnterminatedComment { line }
+            | PgnError::UnbalancedVariation { line }
+            | PgnError::MisplacedVariation { line }
+            | PgnError::UnexpectedToken { line, .. }
+            | PgnError::InvalidFen { line, .. }
+            | PgnError::InvalidMove { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Io(err) => write!(f, "Couldn't read the PGN: {}", err),
+            PgnError::InvalidTag { line } => write!(f, "Invalid tag pair on line {}", line),
+            PgnError::UnterminatedComment { line } => {
+                write!(f, "Comment on line {} is never closed", line)
+            }
+            PgnError::UnbalancedVariation { line } => {
+                write!(f, "Unbalanced variation parentheses on line {}", line)
+            }
+            PgnError::MisplacedVariation { line } => {
+                write!(f, "Variation on line {} doesn't follow a move", line)
+            }
+            PgnError::UnexpectedToken { line, token } => {
+                write!(f, "Unexpected '{}' on line {}", token, line)
+            }
+            PgnError::InvalidFen { line, error } => write!(f, "Bad FEN tag on line {}: {}", line, error),
+            PgnError::InvalidMove { line, error } => write!(f, "Line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+/**
+ * Why a line of an EPD file couldn't be read.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum EpdError {
+    // EPD always has the first four FEN fields, even though FEN lets some of them go.
+    MissingField(FenField),
+    InvalidFen(FenError),
+    UnterminatedString,
+    // an opcode like bm or dm with nothing after it.
+    MissingOperand { opcode: String },
+    InvalidOperand { opcode: String, operand: String },
+    InvalidMove { opcode: String, error: SanError },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::MissingField(field) => write!(f, "The EPD stops before its {} field", field),
+            EpdError::InvalidFen(error) => write!(f, "Bad position: {}", error),
+            EpdError::UnterminatedString => write!(f, "A quoted string is never closed"),
+            EpdError::MissingOperand { opcode } => write!(f, "{} needs an operand", opcode),
+            EpdError::InvalidOperand { opcode, operand } => {
+                write!(f, "'{}' isn't a valid {} operand", operand, opcode)
+            }
+            EpdError::InvalidMove { opcode, error } => write!(f, "{}: {}", opcode, error),
+        }
+    }
+}
+
+impl Error for EpdError {}
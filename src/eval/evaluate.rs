@@ -0,0 +1,180 @@
+use crate::bitboards::attacks::{attack_map, king_attacks};
+use crate::chess_state::ChessState;
+use crate::eval::piece_square_tables::{piece_square_tables, table_index};
+use crate::types::{BoardPiece, ChessPieces, PieceColor};
+
+// (middlegame, endgame) material values in centipawns.
+const PAWN_VALUE: (i32, i32) = (82, 94);
+const KNIGHT_VALUE: (i32, i32) = (337, 281);
+const BISHOP_VALUE: (i32, i32) = (365, 297);
+const ROOK_VALUE: (i32, i32) = (477, 512);
+const QUEEN_VALUE: (i32, i32) = (1025, 936);
+
+// per square next to a king (or under it) the other side attacks.
+const KING_ZONE_ATTACK_PENALTY: i32 = 12;
+
+// the starting position is worth 24 phase points, bare kings and pawns 0.
+pub const MAX_PHASE: i32 = 24;
+
+pub fn material_value(piece_type: ChessPieces) -> (i32, i32) {
+    match piece_type {
+        ChessPieces::Pawns => PAWN_VALUE,
+        ChessPieces::Knights => KNIGHT_VALUE,
+        ChessPieces::Bishops => BISHOP_VALUE,
+        ChessPieces::Rooks => ROOK_VALUE,
+        ChessPieces::Queens => QUEEN_VALUE,
+        ChessPieces::Kings | ChessPieces::Empty => (0, 0),
+    }
+}
+
+fn phase_weight(piece_type: ChessPieces) -> i32 {
+    match piece_type {
+        ChessPieces::Knights | ChessPieces::Bishops => 1,
+        ChessPieces::Rooks => 2,
+        ChessPieces::Queens => 4,
+        _ => 0,
+    }
+}
+
+/**
+ * How much of the middlegame is left, from MAX_PHASE (all pieces on the board) down to 0.
+ * Early promotions can push the count past the start, so it's capped.
+ */
+pub fn game_phase(board: &[BoardPiece; 64]) -> i32 {
+    let phase: i32 = board.iter().map(|piece| phase_weight(piece.piece_type)).sum();
+    phase.min(MAX_PHASE)
+}
+
+/**
+ * Scores a position in centipawns from the side to move's point of view.
+ *
+ * Material and piece-square tables are added up twice, once for the middlegame and once
+ *      for the endgame, then blended by how many pieces are left (tapered eval).
+ * King safety only counts in the middlegame, in the endgame the king wants to be out and about.
+ * Both colors read the same tables mirrored, so swapping the colors of a position
+ *      (and who's to move) evaluates to the exact same score.
+ *
+ * https://www.chessprogramming.org/Tapered_Eval
+ */
+pub fn evaluate(state: &ChessState) -> i32 {
+    let mut middlegame = 0;
+    let mut endgame = 0;
+
+    for (square, piece) in state.board.iter().enumerate() {
+        let (mg_table, eg_table) = match piece_square_tables(piece.piece_type) {
+            Some(tables) => tables,
+            None => continue,
+        };
+        let sign = match piece.piece_color {
+            PieceColor::White => 1,
+            PieceColor::Black => -1,
+            PieceColor::None => continue,
+        };
+        let index = table_index(square, piece.piece_color == PieceColor::White);
+        let (mg_value, eg_value) = material_value(piece.piece_type);
+        middlegame += sign * (mg_value + mg_table[index]);
+        endgame += sign * (eg_value + eg_table[index]);
+    }
+
+    middlegame += king_safety(state);
+
+    let phase = game_phase(&state.board);
+    let score = (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+    match state.color_to_move {
+        PieceColor::Black => -score,
+        _ => score,
+    }
+}
+
+/**
+ * A penalty for every square around each king the enemy attacks, from white's point of view.
+ */
+pub fn king_safety(state: &ChessState) -> i32 {
+    let mut score = 0;
+    for (color, enemy, sign) in [(PieceColor::White, PieceColor::Black, 1), (PieceColor::Black, PieceColor::White, -1)] {
+        let kings = state.bitboards.pieces(&color, ChessPieces::Kings);
+        if kings == 0 {
+            continue;
+        }
+        let king_zone = kings | king_attacks(kings.trailing_zeros() as usize);
+        let attacked = (attack_map(state, &enemy) & king_zone).count_ones() as i32;
+        score -= sign * attacked * KING_ZONE_ATTACK_PENALTY;
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    fn evaluate_fen(fen: &str) -> i32 {
+        evaluate(&load_fen_state(fen.to_string()).unwrap())
+    }
+
+    // mirrors the board top to bottom and swaps every color, including whose turn it is.
+    fn flip_colors(state: &ChessState) -> ChessState {
+        let mut flipped = state.clone();
+        for (square, piece) in state.board.iter().enumerate() {
+            flipped.board[square ^ 56] = BoardPiece {
+                piece_type: piece.piece_type,
+                piece_color: match piece.piece_color {
+                    PieceColor::White => PieceColor::Black,
+                    PieceColor::Black => PieceColor::White,
+                    PieceColor::None => PieceColor::None,
+                },
+            };
+        }
+        flipped.color_to_move = match state.color_to_move {
+            PieceColor::White => PieceColor::Black,
+            _ => PieceColor::White,
+        };
+        flipped.rebuild_bitboards();
+        flipped
+    }
+
+    #[test]
+    fn flipped_positions_score_the_same_for_the_other_side() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "6k1/b7/8/8/5p2/7p/7P/7K w - - 0 54",
+        ];
+        for fen in fens {
+            let state = load_fen_state(fen.to_string()).unwrap();
+            let flipped = flip_colors(&state);
+            assert_eq!(evaluate(&state), evaluate(&flipped), "{}", fen);
+            // the same position with the other side to move is the negated score.
+            let mut other_side = state.clone();
+            other_side.color_to_move = flipped.color_to_move;
+            assert_eq!(evaluate(&state), -evaluate(&other_side), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn scores_follow_material_and_phase() {
+        assert_eq!(evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), 0);
+        // a queen up is good for white and bad for black.
+        assert!(evaluate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1") > 800);
+        assert!(evaluate_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1") < -800);
+
+        let start = load_fen_state("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string()).unwrap();
+        let kings = load_fen_state("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1".to_string()).unwrap();
+        assert_eq!(game_phase(&start.board), MAX_PHASE);
+        assert_eq!(game_phase(&kings.board), 0);
+        // a centralised king is only worth something in the endgame.
+        assert!(evaluate_fen("8/8/8/8/4K3/8/8/k7 w - - 0 1") > evaluate_fen("8/8/8/8/8/8/8/k3K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn attacked_kings_are_unsafe() {
+        let start = load_fen_state("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string()).unwrap();
+        assert_eq!(king_safety(&start), 0);
+        // the queen on h4 hits f2 and e1 next to the white king (after 1. f3 e5 2. g4).
+        let exposed = load_fen_state("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3".to_string()).unwrap();
+        assert!(king_safety(&exposed) < 0);
+        assert_eq!(king_safety(&exposed), -king_safety(&flip_colors(&exposed)));
+    }
+}
@@ -0,0 +1,68 @@
+/**
+ * The Chess960 starting position with Scharnagl number `number` (0 to 959) as a FEN string.
+ * 518 is the normal starting position.
+ *
+ * The number picks, in order: the light squared bishop, the dark squared bishop, the queen
+ *      out of the six squares left, the two knights out of the five left, and the king goes
+ *      between the rooks on the last three.
+ * Both sides get all their castling rights, written as KQkq since the rooks are outermost.
+ *
+ * https://www.chessprogramming.org/Reinhard_Scharnagl#Chess960_numbering
+ */
+pub fn chess960_position(number: u32) -> Option<String> {
+    // which two of the five squares left over the knights go on.
+    const KNIGHT_PLACEMENTS: [(usize, usize); 10] =
+        [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+    if number >= 960 {
+        return None;
+    }
+
+    let mut rank = [' '; 8];
+    let mut rest = number as usize;
+    rank[rest % 4 * 2 + 1] = 'b';
+    rest /= 4;
+    rank[rest % 4 * 2] = 'b';
+    rest /= 4;
+    let empty_squares = |rank: &[char; 8]| (0..8).filter(|file| rank[*file] == ' ').collect::<Vec<_>>();
+    rank[empty_squares(&rank)[rest % 6]] = 'q';
+    rest /= 6;
+
+    let (first_knight, second_knight) = KNIGHT_PLACEMENTS[rest];
+    let empty = empty_squares(&rank);
+    rank[empty[first_knight]] = 'n';
+    rank[empty[second_knight]] = 'n';
+    for (file, piece) in empty_squares(&rank).into_iter().zip(['r', 'k', 'r']) {
+        rank[file] = piece;
+    }
+
+    let black: String = rank.iter().collect();
+    Some(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        black,
+        black.to_ascii_uppercase()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::START_POSITION;
+    use crate::helpers::fen::validate::load_valid_fen_state;
+
+    #[test]
+    fn scharnagl_numbers() {
+        assert_eq!(chess960_position(518).unwrap(), START_POSITION);
+        assert_eq!(chess960_position(0).unwrap(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(chess960_position(959).unwrap(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+        assert_eq!(chess960_position(960), None);
+
+        // every number gives a different, valid position.
+        let mut positions: Vec<String> = (0..960).map(|number| chess960_position(number).unwrap()).collect();
+        for fen in &positions {
+            assert!(load_valid_fen_state(fen.clone()).is_ok(), "{}", fen);
+        }
+        positions.sort();
+        positions.dedup();
+        assert_eq!(positions.len(), 960);
+    }
+}
@@ -1,10 +1,12 @@
+use crate::bitboards::attacks::attack_map;
 use crate::chess_state::ChessState;
+use crate::helpers::color::switch_color;
 use crate::types::{BoardPiece, ChessPieces, Move, MoveType, PieceColor};
 
 // https://crates.io/crates/colored
 use colored::*;
 
-pub fn display_chess_tui(state: &ChessState, movement: &Vec<Move>) {
+pub fn display_chess_tui(state: &ChessState, movement: &[Move]) {
     let turn_color = match state.color_to_move {
         PieceColor::White => "White",
         PieceColor::Black => "Black",
@@ -14,6 +16,14 @@ pub fn display_chess_tui(state: &ChessState, movement: &Vec<Move>) {
     let mut print_index = 1;
     let mut position = 0;
     print!("\n{turn_color}'s turn\n");
+    if state.check_info.is_double_check() {
+        println!("{}", "Double check!".truecolor(255, 80, 80));
+    } else if state.check_info.is_check() {
+        println!("{}", "Check!".truecolor(255, 80, 80));
+    }
+    // the side to move's pieces the other side could take, in orange.
+    let threatened = attack_map(state, &switch_color(&state.color_to_move))
+        & state.bitboards.color(&state.color_to_move);
 
     for i in (0..8).rev() {
         let letter = match i {
@@ -29,7 +39,7 @@ pub fn display_chess_tui(state: &ChessState, movement: &Vec<Move>) {
         };
         print!(" {} ", letter.truecolor(105, 105, 105))
     }
-    println!("");
+    println!();
 
     for square in state.board {
         let newline = if print_index % 8 == 0 {
@@ -54,6 +64,8 @@ pub fn display_chess_tui(state: &ChessState, movement: &Vec<Move>) {
                     MoveType::Promotion => (255, 255, 255),
                     MoveType::Piercing => (255, 255, 255),
                 }
+            } else if threatened & 1 << position != 0 {
+                (255, 165, 0)
             } else {
                 (100, 100, 100)
             };
@@ -70,10 +82,10 @@ pub fn display_chess_tui(state: &ChessState, movement: &Vec<Move>) {
         print_index += 1;
         position += 1;
     }
-    print!("\n");
+    println!();
 }
 
-fn find_castling_moves(moves: &Vec<Move>) -> Vec<&Move> {
+fn find_castling_moves(moves: &[Move]) -> Vec<&Move> {
     moves
         .iter()
         .filter(|&&mov| mov.move_type == MoveType::Castle)
@@ -81,25 +93,25 @@ fn find_castling_moves(moves: &Vec<Move>) -> Vec<&Move> {
 }
 
 pub fn format_piece(square: BoardPiece) -> ColoredString {
-    return match square.piece_color {
+    match square.piece_color {
         PieceColor::White => match square.piece_type {
-            ChessPieces::Kings => format!("K"),
-            ChessPieces::Queens => format!("Q"),
-            ChessPieces::Rooks => format!("R"),
-            ChessPieces::Bishops => format!("B"),
-            ChessPieces::Knights => format!("N"),
-            ChessPieces::Pawns => format!("P"),
-            ChessPieces::Empty => format!(" "),
+            ChessPieces::Kings => "K",
+            ChessPieces::Queens => "Q",
+            ChessPieces::Rooks => "R",
+            ChessPieces::Bishops => "B",
+            ChessPieces::Knights => "N",
+            ChessPieces::Pawns => "P",
+            ChessPieces::Empty => " ",
         }.red(),
         PieceColor::Black => match square.piece_type {
-            ChessPieces::Kings => format!("k"),
-            ChessPieces::Queens => format!("q"),
-            ChessPieces::Rooks => format!("r"),
-            ChessPieces::Bishops => format!("b"),
-            ChessPieces::Knights => format!("n"),
-            ChessPieces::Pawns => format!("p"),
-            ChessPieces::Empty => format!(" "),
+            ChessPieces::Kings => "k",
+            ChessPieces::Queens => "q",
+            ChessPieces::Rooks => "r",
+            ChessPieces::Bishops => "b",
+            ChessPieces::Knights => "n",
+            ChessPieces::Pawns => "p",
+            ChessPieces::Empty => " ",
         }.blue(),
-        PieceColor::None => ColoredString::from(format!(" ")),
-    };
+        PieceColor::None => ColoredString::from(" "),
+    }
 }
@@ -0,0 +1,239 @@
+use std::mem;
+
+use crate::chess_state::ChessState;
+use crate::error_types::{EpdError, FenField};
+use crate::helpers::fen::validate::load_valid_fen_state;
+use crate::helpers::san::san_to_move;
+use crate::types::Move;
+
+// the FEN fields every EPD line starts with, the clocks are left out.
+const FIELDS: [FenField; 4] = [FenField::Board, FenField::Turn, FenField::Castling, FenField::EnPassant];
+
+/**
+ * One line of an EPD file, a position and what's known about it.
+ * Only bm, am, id, c0, dm and acd are kept, any other opcode is skipped.
+ * bm, am and dm are what a search gets scored on, acd is only kept for reference.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpdRecord {
+    pub state: ChessState,
+    // bm, playing any one of them is right.
+    pub best_moves: Vec<Move>,
+    // am, playing any one of them is wrong.
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    // c0, the primary comment.
+    pub comment: Option<String>,
+    // dm, the side to move mates in this many moves.
+    pub direct_mate: Option<u32>,
+    // acd, how deep the position was analysed.
+    pub analysis_depth: Option<u32>,
+}
+
+impl EpdRecord {
+    /**
+     * Whether there's a bm, am or dm to check a search against.
+     */
+    pub fn has_answer(&self) -> bool {
+        !self.best_moves.is_empty() || !self.avoid_moves.is_empty() || self.direct_mate.is_some()
+    }
+
+    /**
+     * A search passes when its move is one of the best moves (if there are any) and none of the
+     *      ones to avoid, and with dm it also has to have found the mate in that many moves.
+     * mate_in is the search's, as search::alpha_beta::mate_in gives it.
+     */
+    pub fn is_solved_by(&self, mov: &Move, mate_in: Option<i32>) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(mov))
+            && !self.avoid_moves.contains(mov)
+            && self.direct_mate.is_none_or(|moves| mate_in == Some(moves as i32))
+    }
+}
+
+/**
+ * Reads one EPD line, like:
+ *      r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5; id "Spanish";
+ *
+ * Moves are SAN and checked against the position, so a typo in a test suite shows up here
+ *      instead of as a position nobody can ever pass.
+ * The semicolon after the last operation is often left off, that's fine.
+ */
+pub fn parse_epd(line: &str) -> Result<EpdRecord, EpdError> {
+    let mut rest = line.trim();
+    let mut fields = Vec::with_capacity(FIELDS.len());
+    for field in FIELDS {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(EpdError::MissingField(field));
+        }
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let state = load_valid_fen_state(fields.join(" ")).map_err(EpdError::InvalidFen)?;
+    let mut record = EpdRecord {
+        state,
+        best_moves: Vec::new(),
+        avoid_moves: Vec::new(),
+        id: None,
+        comment: None,
+        direct_mate: None,
+        analysis_depth: None,
+    };
+    for (opcode, operands) in split_operations(rest)? {
+        match opcode.as_str() {
+            "bm" => record.best_moves = parse_moves(&record.state, &opcode, &operands)?,
+            "am" => record.avoid_moves = parse_moves(&record.state, &opcode, &operands)?,
+            "id" => record.id = Some(first_operand(&opcode, &operands)?.to_string()),
+            "c0" => record.comment = Some(first_operand(&opcode, &operands)?.to_string()),
+            "dm" => record.direct_mate = Some(parse_number(&opcode, &operands)?),
+            "acd" => record.analysis_depth = Some(parse_number(&opcode, &operands)?),
+            _ => (),
+        }
+    }
+    Ok(record)
+}
+
+// the operations after the position, each an opcode and its operands.
+// quoted strings are one operand and can hold spaces and semicolons.
+fn split_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut finish_operation = |words: &mut Vec<String>| {
+        let mut operands = mem::take(words);
+        if !operands.is_empty() {
+            let opcode = operands.remove(0);
+            operations.push((opcode, operands));
+        }
+    };
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => finish_operation(&mut words),
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => string.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                words.push(string);
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                words.push(word);
+            }
+        }
+    }
+    finish_operation(&mut words);
+    Ok(operations)
+}
+
+fn first_operand<'a>(opcode: &str, operands: &'a [String]) -> Result<&'a str, EpdError> {
+    operands
+        .first()
+        .map(String::as_str)
+        .ok_or_else(|| EpdError::MissingOperand { opcode: opcode.to_string() })
+}
+
+fn parse_number(opcode: &str, operands: &[String]) -> Result<u32, EpdError> {
+    let operand = first_operand(opcode, operands)?;
+    operand.parse().map_err(|_| EpdError::InvalidOperand {
+        opcode: opcode.to_string(),
+        operand: operand.to_string(),
+    })
+}
+
+fn parse_moves(
+    state: &ChessState,
+    opcode: &str,
+    operands: &[String],
+) -> Result<Vec<Move>, EpdError> {
+    first_operand(opcode, operands)?;
+    operands
+        .iter()
+        .map(|san| {
+            san_to_move(state, san).map_err(|error| EpdError::InvalidMove {
+                opcode: opcode.to_string(),
+                error,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_types::SanError;
+    use crate::helpers::san::move_to_san;
+
+    #[test]
+    fn parses_opcodes() {
+        let line = r#"r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am Qe2; id "open; game"; c0 "two good moves"; acd 12; hmvc 2"#;
+        let record = parse_epd(line).unwrap();
+        let sans = |moves: &[Move]| {
+            moves.iter().map(|mov| move_to_san(&record.state, mov)).collect::<Vec<_>>()
+        };
+        assert_eq!(sans(&record.best_moves), ["Bb5", "Bc4"]);
+        assert_eq!(sans(&record.avoid_moves), ["Qe2"]);
+        assert_eq!(record.id.as_deref(), Some("open; game"));
+        assert_eq!(record.comment.as_deref(), Some("two good moves"));
+        assert_eq!(record.analysis_depth, Some(12));
+        assert_eq!(record.direct_mate, None);
+
+        assert!(record.is_solved_by(&record.best_moves[1], None));
+        assert!(!record.is_solved_by(&record.avoid_moves[0], None));
+
+        let mate = parse_epd("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - dm 1; bm Ra8#;").unwrap();
+        assert_eq!(mate.direct_mate, Some(1));
+        assert_eq!(mate.best_moves.len(), 1);
+        assert!(mate.is_solved_by(&mate.best_moves[0], Some(1)));
+        assert!(!mate.is_solved_by(&mate.best_moves[0], None));
+
+        // dm alone is enough to score a search on.
+        let mate = parse_epd("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - dm 1;").unwrap();
+        assert!(mate.has_answer());
+    }
+
+    #[test]
+    fn broken_records() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
+        let parse = |operations: &str| parse_epd(&format!("{} {}", start, operations));
+
+        assert_eq!(
+            parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq"),
+            Err(EpdError::MissingField(FenField::EnPassant))
+        );
+        assert!(matches!(
+            parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq -"),
+            Err(EpdError::InvalidFen(_))
+        ));
+        assert_eq!(parse(r#"id "never closed;"#), Err(EpdError::UnterminatedString));
+        assert_eq!(parse("bm;"), Err(EpdError::MissingOperand { opcode: "bm".to_string() }));
+        assert_eq!(
+            parse("dm two;"),
+            Err(EpdError::InvalidOperand { opcode: "dm".to_string(), operand: "two".to_string() })
+        );
+        assert_eq!(
+            parse("bm e5;"),
+            Err(EpdError::InvalidMove {
+                opcode: "bm".to_string(),
+                error: SanError::IllegalMove("e5".to_string())
+            })
+        );
+        // no operations at all is still a position.
+        assert!(!parse("").unwrap().has_answer());
+    }
+}
@@ -1,11 +1,166 @@
-
-use crate::chess_state::ChessState;
+use crate::chess_state::{square_to_algebraic, ChessState};
 use crate::types::{BoardPiece, Castle, ChessPieces, PieceColor};
 
 /**
- * Doesn't support the entire FEN string yet.
- * Halfmoves (essential for tracking stalemates) don't work yet.
+ * Writes the state back out as a six field FEN string, the reverse of load_fen_state.
+ * Chess960 castling rights come out as X-FEN, which is plain KQkq unless a rook further
+ *      out on the same side makes that ambiguous.
+ */
+pub fn save_fen_state(state: &ChessState) -> Result<String, &'static str> {
+    save_fen(state, false)
+}
+
+/**
+ * save_fen_state with Shredder-FEN castling rights, always the rook files like HAha.
  */
-pub fn save_fen_state(state: ChessState) -> Result<String, &'static str> {
-    Ok("".to_string())
+pub fn save_shredder_fen_state(state: &ChessState) -> Result<String, &'static str> {
+    save_fen(state, true)
+}
+
+fn save_fen(state: &ChessState, is_shredder: bool) -> Result<String, &'static str> {
+    let board = save_position_to_fen(&state.board)?;
+    let turn = match state.color_to_move {
+        PieceColor::White => "w",
+        PieceColor::Black => "b",
+        PieceColor::None => return Err("No color to move."),
+    };
+    let castle = save_castle(&state.is_able_to_castle, &state.board, is_shredder);
+    let en_passant = match state.en_passant {
+        Some(square) => square_to_algebraic(square),
+        None => "-".to_string(),
+    };
+    Ok(format!(
+        "{} {} {} {} {} {}",
+        board, turn, castle, en_passant, state.halfmove_clock, state.fullmove_number
+    ))
+}
+
+fn save_castle(castle: &Castle, board: &[BoardPiece; 64], is_shredder: bool) -> String {
+    let mut part = String::new();
+    let rights = [
+        (PieceColor::White, true, 'K'),
+        (PieceColor::White, false, 'Q'),
+        (PieceColor::Black, true, 'k'),
+        (PieceColor::Black, false, 'q'),
+    ];
+    for (color, is_kingside, letter) in rights {
+        let rook_file = match castle.rook_file(&color, is_kingside) {
+            Some(rook_file) => rook_file,
+            None => continue,
+        };
+        let home_rank = if color == PieceColor::White { 0 } else { 56 };
+        // X-FEN only names the file when K or Q would pick another rook.
+        let further_out = if is_kingside { rook_file + 1..8 } else { 0..rook_file };
+        let is_outermost = !further_out.into_iter().any(|file| {
+            board[home_rank + file].piece_type == ChessPieces::Rooks && board[home_rank + file].piece_color == color
+        });
+        if is_outermost && !is_shredder {
+            part.push(letter);
+        } else {
+            let file = (b'a' + rook_file as u8) as char;
+            part.push(if color == PieceColor::White { file.to_ascii_uppercase() } else { file });
+        }
+    }
+    if part.is_empty() {
+        part.push('-');
+    }
+    part
+}
+
+fn save_position_to_fen(board: &[BoardPiece; 64]) -> Result<String, &'static str> {
+    let mut fen = String::new();
+
+    for rank in (0..8).rev() {
+        let mut empty_squares = 0;
+        for file in 0..8 {
+            let piece = board[rank * 8 + file];
+            let letter = match piece.piece_type {
+                ChessPieces::Pawns => 'p',
+                ChessPieces::Kings => 'k',
+                ChessPieces::Queens => 'q',
+                ChessPieces::Bishops => 'b',
+                ChessPieces::Knights => 'n',
+                ChessPieces::Rooks => 'r',
+                ChessPieces::Empty => {
+                    empty_squares += 1;
+                    continue;
+                }
+            };
+            if empty_squares > 0 {
+                fen.push_str(&empty_squares.to_string());
+                empty_squares = 0;
+            }
+            match piece.piece_color {
+                PieceColor::White => fen.push(letter.to_ascii_uppercase()),
+                PieceColor::Black => fen.push(letter),
+                PieceColor::None => return Err("Piece on the board has no color."),
+            }
+        }
+        if empty_squares > 0 {
+            fen.push_str(&empty_squares.to_string());
+        }
+        if rank > 0 {
+            fen.push('/');
+        }
+    }
+    Ok(fen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    const FENS: [&str; 14] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 17 42",
+        "6k1/b7/8/8/5p2/7p/7P/7K w - - 0 54",
+        "8/8/8/8/8/8/8/K6k b - - 99 150",
+        "4k3/8/8/8/8/8/8/4K2R w K - 3 1000",
+        "1n2k1n1/8/8/8/8/8/8/1N2K1N1 b - - 12 7",
+    ];
+
+    #[test]
+    fn save_load_round_trip() {
+        for fen in FENS {
+            let state = load_fen_state(fen.to_string()).unwrap();
+            assert_eq!(save_fen_state(&state).unwrap(), fen);
+        }
+    }
+
+    #[test]
+    fn chess960_castling_rights() {
+        // the same rights read from Shredder-FEN and X-FEN.
+        let shredder = load_fen_state("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9".to_string()).unwrap();
+        let x_fen = load_fen_state("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w KQ - 1 9".to_string()).unwrap();
+        assert_eq!(shredder, x_fen);
+        assert_eq!(shredder.is_able_to_castle.white_kingside, Some(6));
+        assert_eq!(shredder.is_able_to_castle.white_queenside, Some(4));
+        assert_eq!(save_fen_state(&shredder).unwrap(), "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w KQ - 1 9");
+        assert_eq!(save_shredder_fen_state(&x_fen).unwrap(), "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9");
+
+        // X-FEN names the file when there's another rook further out, Q means the outer one.
+        let inner = load_fen_state("1r2k2r/8/8/8/8/8/8/RR2K2R w KBk - 0 1".to_string()).unwrap();
+        assert_eq!(inner.is_able_to_castle.white_queenside, Some(1));
+        assert_eq!(save_fen_state(&inner).unwrap(), "1r2k2r/8/8/8/8/8/8/RR2K2R w KBk - 0 1");
+        assert_eq!(save_shredder_fen_state(&inner).unwrap(), "1r2k2r/8/8/8/8/8/8/RR2K2R w HBh - 0 1");
+        let outer = load_fen_state("1r2k2r/8/8/8/8/8/8/RR2K2R w Qq - 0 1".to_string()).unwrap();
+        assert_eq!(outer.is_able_to_castle.white_queenside, Some(0));
+        assert_eq!(outer.is_able_to_castle.black_queenside, Some(1));
+        assert_eq!(save_fen_state(&outer).unwrap(), "1r2k2r/8/8/8/8/8/8/RR2K2R w Qq - 0 1");
+    }
+
+    #[test]
+    fn missing_clocks_default() {
+        let state = load_fen_state("8/8/8/8/8/8/8/K6k w - -".to_string()).unwrap();
+        assert_eq!(save_fen_state(&state).unwrap(), "8/8/8/8/8/8/8/K6k w - - 0 1");
+    }
 }
@@ -1,105 +1,312 @@
-use crate::chess_state::ChessState;
+use crate::chess_state::{convert_algebraic_snippet, ChessState};
+use crate::error_types::{FenError, FenField};
+use crate::helpers::zobrist::compute_zobrist_key;
 use crate::types::{BoardPiece, Castle, ChessPieces, PieceColor};
 
+const FIELDS: [FenField; 6] = [
+    FenField::Board,
+    FenField::Turn,
+    FenField::Castling,
+    FenField::EnPassant,
+    FenField::HalfmoveClock,
+    FenField::FullmoveNumber,
+];
+
 /**
- * Doesn't support the entire FEN string yet.
- * Halfmoves (essential for tracking stalemates) don't work yet.
+ * Loads all six FEN fields: board, turn, castling, en passant, halfmove clock and fullmove number.
+ * Trailing fields after the turn can be left out, the clocks default to "0 1".
+ * Chess960 positions can come in as X-FEN or Shredder-FEN, see parse_castle.
+ *
+ * This only checks the syntax, see validate::validate_fen_state for whether the position makes sense.
  */
-pub fn load_fen_state(fen: String) -> Result<ChessState, &'static str> {
-    let fen: Vec<&str> = fen.trim().split_whitespace().collect();
-    if fen.len() < 2 {
-        return Err("Invalid FEN string");
+pub fn load_fen_state(fen: String) -> Result<ChessState, FenError> {
+    let fields = split_fields(&fen);
+    if fields.len() > FIELDS.len() {
+        return Err(FenError::TooManyFields {
+            offset: fields[FIELDS.len()].0,
+        });
+    }
+    // the board and turn can't be left out.
+    if fields.len() < 2 {
+        return Err(FenError::MissingField {
+            field: FIELDS[fields.len()],
+        });
     }
 
-    let board = if let Some(fen_board) = fen.get(0) {
-        load_position_from_fen(fen_board.to_string())?
-    } else {
-        [Default::default(); 64]
-    };
-
-    let mut state = ChessState {
-        board,
-        ..Default::default()
-    };
-
-    for (i, &part) in fen.iter().skip(1).enumerate() {
-        match i {
-            0 => state.color_to_move = parse_turn(part)?,
-            1 => state.is_able_to_castle = parse_castle(part)?,
-            _ => {}
+    let mut state = ChessState::default();
+    for (&(offset, part), field) in fields.iter().zip(FIELDS) {
+        match field {
+            FenField::Board => state.board = load_position_from_fen(part, offset)?,
+            FenField::Turn => state.color_to_move = parse_turn(part, offset)?,
+            FenField::Castling => state.is_able_to_castle = parse_castle(part, offset, &state.board)?,
+            FenField::EnPassant => state.en_passant = parse_en_passant(part, offset)?,
+            FenField::HalfmoveClock => {
+                state.halfmove_clock = parse_number(part, offset, FenField::HalfmoveClock)?
+            }
+            FenField::FullmoveNumber => {
+                state.fullmove_number = parse_number(part, offset, FenField::FullmoveNumber)?;
+                if state.fullmove_number == 0 {
+                    return Err(FenError::InvalidNumber { field, offset });
+                }
+            }
         }
     }
+    state.rebuild_bitboards();
+    state.zobrist_key = compute_zobrist_key(&state);
     Ok(state)
 }
 
-fn parse_turn(part: &str) -> Result<PieceColor, &'static str> {
-    match part.chars().nth(0).unwrap_or_else(|| 'm') {
-        'w' => Ok(PieceColor::White),
-        'b' => Ok(PieceColor::Black),
-        _ => Err("Wrong input for turns."),
+/**
+ * Splits on whitespace, keeping the character offset each field starts at.
+ */
+fn split_fields(fen: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut field_start: Option<(usize, usize)> = None; // (character offset, byte offset)
+
+    for (offset, (byte_offset, ch)) in fen.char_indices().enumerate() {
+        match (ch.is_whitespace(), field_start) {
+            (false, None) => field_start = Some((offset, byte_offset)),
+            (true, Some((start, byte_start))) => {
+                fields.push((start, &fen[byte_start..byte_offset]));
+                field_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some((start, byte_start)) = field_start {
+        fields.push((start, &fen[byte_start..]));
     }
+    fields
 }
 
-fn parse_castle(part: &str) -> Result<Castle, &'static str> {
-    let mut castle = Castle {
-        black_queenside: false,
-        black_kingside: false,
-        white_queenside: false,
-        white_kingside: false,
+fn parse_turn(part: &str, offset: usize) -> Result<PieceColor, FenError> {
+    let mut chars = part.chars();
+    let turn = match chars.next() {
+        Some('w') => PieceColor::White,
+        Some('b') => PieceColor::Black,
+        character => {
+            return Err(FenError::InvalidCharacter {
+                field: FenField::Turn,
+                offset,
+                character: character.unwrap_or(' '),
+            })
+        }
     };
+    if let Some(character) = chars.next() {
+        return Err(FenError::InvalidCharacter {
+            field: FenField::Turn,
+            offset: offset + 1,
+            character,
+        });
+    }
+    Ok(turn)
+}
+
+/**
+ * Reads normal KQkq castling rights as well as X-FEN and Shredder-FEN ones for Chess960.
+ *
+ * K and Q castle with the outermost rook on that side of the king, a file letter names
+ *      the rook instead (A-H for white, a-h for black), which X-FEN only uses when
+ *      there's another rook further out and Shredder-FEN always uses.
+ * A right with no rook to go with it gets the corner, validate_fen_state catches those.
+ */
+fn parse_castle(part: &str, offset: usize, board: &[BoardPiece; 64]) -> Result<Castle, FenError> {
+    let mut castle = Castle::default();
+
+    for (index, char) in part.chars().enumerate() {
+        if char == '-' && part.len() == 1 {
+            continue;
+        }
+        let (color, home_rank) = match char.is_ascii_uppercase() {
+            true => (PieceColor::White, 0),
+            false => (PieceColor::Black, 56),
+        };
+        let is_piece = |file: &usize, piece_type: ChessPieces| {
+            board[home_rank + file].piece_type == piece_type && board[home_rank + file].piece_color == color
+        };
+        let king_file = (0..8).find(|file| is_piece(file, ChessPieces::Kings)).unwrap_or(4);
+        let mut rook_files = (0..8).filter(|file| is_piece(file, ChessPieces::Rooks));
 
-    for char in part.chars() {
-        match char {
-            'Q' => castle.white_queenside = true,
-            'K' => castle.white_kingside = true,
-            'q' => castle.black_queenside = true,
-            'k' => castle.black_kingside = true,
-            '-' => (),
-            _ => return Err("Invalid castle notation!"),
+        let (is_kingside, rook_file) = match char.to_ascii_lowercase() {
+            'k' => (true, rook_files.rfind(|file| *file > king_file).unwrap_or(7)),
+            'q' => (false, rook_files.find(|file| *file < king_file).unwrap_or(0)),
+            file @ 'a'..='h' => {
+                let file = file as usize - 'a' as usize;
+                (file > king_file, file)
+            }
+            _ => {
+                return Err(FenError::InvalidCharacter {
+                    field: FenField::Castling,
+                    offset: offset + index,
+                    character: char,
+                })
+            }
+        };
+        match (color, is_kingside) {
+            (PieceColor::White, true) => castle.white_kingside = Some(rook_file),
+            (PieceColor::White, false) => castle.white_queenside = Some(rook_file),
+            (_, true) => castle.black_kingside = Some(rook_file),
+            (_, false) => castle.black_queenside = Some(rook_file),
         }
     }
     Ok(castle)
 }
 
-fn load_position_from_fen(fen: String) -> Result<[BoardPiece; 64], &'static str> {
+fn parse_en_passant(part: &str, offset: usize) -> Result<Option<i16>, FenError> {
+    if part == "-" {
+        return Ok(None);
+    }
+    // a pawn can only skip over the third or sixth rank.
+    for (index, character) in part.chars().enumerate() {
+        let is_valid = match index {
+            0 => ('a'..='h').contains(&character),
+            1 => matches!(character, '3' | '6'),
+            _ => false,
+        };
+        if !is_valid {
+            return Err(FenError::InvalidCharacter {
+                field: FenField::EnPassant,
+                offset: offset + index,
+                character,
+            });
+        }
+    }
+    if part.len() < 2 {
+        return Err(FenError::FieldTooShort {
+            field: FenField::EnPassant,
+            offset: offset + part.len(),
+        });
+    }
+    // both characters were checked above.
+    Ok(Some(convert_algebraic_snippet(part).unwrap() as i16))
+}
+
+fn parse_number(part: &str, offset: usize, field: FenField) -> Result<u32, FenError> {
+    if let Some((index, character)) = part.chars().enumerate().find(|(_, ch)| !ch.is_ascii_digit()) {
+        return Err(FenError::InvalidCharacter {
+            field,
+            offset: offset + index,
+            character,
+        });
+    }
+    part.parse().map_err(|_| FenError::InvalidNumber { field, offset })
+}
+
+fn load_position_from_fen(fen: &str, offset: usize) -> Result<[BoardPiece; 64], FenError> {
     let mut board: [BoardPiece; 64] = [Default::default(); 64];
     let mut file = 0;
     let mut rank = 7;
 
-    for letter in fen.chars() {
+    for (index, letter) in fen.chars().enumerate() {
+        let letter_offset = offset + index;
         if letter == '/' {
+            if file != 8 {
+                return Err(FenError::RankTooShort { offset: letter_offset });
+            }
+            if rank == 0 {
+                return Err(FenError::WrongRankCount {
+                    offset: letter_offset,
+                    ranks: 9,
+                });
+            }
             file = 0;
             rank -= 1;
+        } else if matches!(letter, '1'..='8') {
+            // movement | 8
+            file += letter.to_digit(10).unwrap();
+            if file > 8 {
+                return Err(FenError::RankTooLong { offset: letter_offset });
+            }
         } else {
-            if letter.is_digit(10) {
-                // movement | 8
-                file += letter.to_digit(10).unwrap();
-            } else {
-                let piece_type = match letter {
-                    'P' => Ok(BoardPiece { piece_type: ChessPieces::Pawns, piece_color: PieceColor::White }),
-                    'K' => Ok(BoardPiece { piece_type: ChessPieces::Kings, piece_color: PieceColor::White }),
-                    'Q' => Ok(BoardPiece { piece_type: ChessPieces::Queens, piece_color: PieceColor::White }),
-                    'B' => Ok(BoardPiece { piece_type: ChessPieces::Bishops, piece_color: PieceColor::White }),
-                    'N' => Ok(BoardPiece { piece_type: ChessPieces::Knights, piece_color: PieceColor::White }),
-                    'R' => Ok(BoardPiece { piece_type: ChessPieces::Rooks, piece_color: PieceColor::White }),
-
-                    'p' => Ok(BoardPiece { piece_type: ChessPieces::Pawns, piece_color: PieceColor::Black }),
-                    'k' => Ok(BoardPiece { piece_type: ChessPieces::Kings, piece_color: PieceColor::Black }),
-                    'q' => Ok(BoardPiece { piece_type: ChessPieces::Queens, piece_color: PieceColor::Black }),
-                    'b' => Ok(BoardPiece { piece_type: ChessPieces::Bishops, piece_color: PieceColor::Black }),
-                    'n' => Ok(BoardPiece { piece_type: ChessPieces::Knights, piece_color: PieceColor::Black }),
-                    'r' => Ok(BoardPiece { piece_type: ChessPieces::Rooks, piece_color: PieceColor::Black }),
-                    _ => Err("You probably got a wrong letter in your fen string."),
-                };
-                let index = usize::try_from(rank * 8 + file).unwrap();
-                match piece_type {
-                    Ok(piece) => board[index] = piece.to_owned(),
-                    Err(err) => return Err(err),
+            let piece_type = match letter {
+                'P' => BoardPiece { piece_type: ChessPieces::Pawns, piece_color: PieceColor::White },
+                'K' => BoardPiece { piece_type: ChessPieces::Kings, piece_color: PieceColor::White },
+                'Q' => BoardPiece { piece_type: ChessPieces::Queens, piece_color: PieceColor::White },
+                'B' => BoardPiece { piece_type: ChessPieces::Bishops, piece_color: PieceColor::White },
+                'N' => BoardPiece { piece_type: ChessPieces::Knights, piece_color: PieceColor::White },
+                'R' => BoardPiece { piece_type: ChessPieces::Rooks, piece_color: PieceColor::White },
+
+                'p' => BoardPiece { piece_type: ChessPieces::Pawns, piece_color: PieceColor::Black },
+                'k' => BoardPiece { piece_type: ChessPieces::Kings, piece_color: PieceColor::Black },
+                'q' => BoardPiece { piece_type: ChessPieces::Queens, piece_color: PieceColor::Black },
+                'b' => BoardPiece { piece_type: ChessPieces::Bishops, piece_color: PieceColor::Black },
+                'n' => BoardPiece { piece_type: ChessPieces::Knights, piece_color: PieceColor::Black },
+                'r' => BoardPiece { piece_type: ChessPieces::Rooks, piece_color: PieceColor::Black },
+                _ => {
+                    return Err(FenError::InvalidCharacter {
+                        field: FenField::Board,
+                        offset: letter_offset,
+                        character: letter,
+                    })
                 }
-                
-                file += 1;
+            };
+            if file >= 8 {
+                return Err(FenError::RankTooLong { offset: letter_offset });
             }
+            board[(rank * 8 + file) as usize] = piece_type;
+            file += 1;
         }
     }
+
+    let end_offset = offset + fen.chars().count();
+    if rank != 0 {
+        return Err(FenError::WrongRankCount {
+            offset: end_offset,
+            ranks: 8 - rank as usize,
+        });
+    }
+    if file != 8 {
+        return Err(FenError::RankTooShort { offset: end_offset });
+    }
     Ok(board)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(fen: &str) -> FenError {
+        load_fen_state(fen.to_string()).unwrap_err()
+    }
+
+    #[test]
+    fn board_errors_point_at_the_offending_character() {
+        assert_eq!(
+            load_error("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::RankTooLong { offset: 17 }
+        );
+        assert_eq!(
+            load_error("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::RankTooShort { offset: 16 }
+        );
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8 w - - 0 1"),
+            FenError::WrongRankCount { offset: 13, ranks: 7 }
+        );
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8/8/8 w - - 0 1"),
+            FenError::WrongRankCount { offset: 15, ranks: 9 }
+        );
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8/K6x w - - 0 1"),
+            FenError::InvalidCharacter { field: FenField::Board, offset: 16, character: 'x' }
+        );
+    }
+
+    #[test]
+    fn field_errors_name_the_field() {
+        assert_eq!(load_error("8/8/8/8/8/8/8/K6k"), FenError::MissingField { field: FenField::Turn });
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8/K6k w - e4 0 1").field(),
+            Some(FenField::EnPassant)
+        );
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8/K6k w - - x 1"),
+            FenError::InvalidCharacter { field: FenField::HalfmoveClock, offset: 24, character: 'x' }
+        );
+        assert_eq!(
+            load_error("8/8/8/8/8/8/8/K6k w - - 0 1 extra"),
+            FenError::TooManyFields { offset: 28 }
+        );
+    }
+}
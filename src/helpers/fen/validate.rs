@@ -0,0 +1,141 @@
+use crate::bitboards::attacks::is_square_attacked;
+use crate::chess_state::ChessState;
+use crate::error_types::FenError;
+use crate::helpers::color::switch_color;
+use crate::helpers::fen::encode::load_fen_state;
+use crate::types::{BoardPiece, ChessPieces, PieceColor};
+
+/**
+ * Loads a FEN and refuses positions that couldn't come up in a real game.
+ */
+pub fn load_valid_fen_state(fen: String) -> Result<ChessState, FenError> {
+    let state = load_fen_state(fen)?;
+    validate_fen_state(&state)?;
+    Ok(state)
+}
+
+/**
+ * Checks a loaded position could come up in a real game.
+ * load_fen_state only checks the syntax, callers that care opt into this.
+ *
+ * - exactly one king per side
+ * - no pawns on the first or eighth rank
+ * - the side that just moved didn't leave its king in check
+ * - castling rights have their king and rook on the home rank, the rook on the right side
+ * - the en passant square sits behind a pawn that just double pushed
+ */
+pub fn validate_fen_state(state: &ChessState) -> Result<(), FenError> {
+    let board = &state.board;
+
+    for color in [PieceColor::White, PieceColor::Black] {
+        let count = board
+            .iter()
+            .filter(|piece| piece.piece_type == ChessPieces::Kings && piece.piece_color == color)
+            .count();
+        if count != 1 {
+            return Err(FenError::WrongKingCount { color, count });
+        }
+    }
+
+    let back_rank_pawn = (0..8)
+        .chain(56..64)
+        .find(|&square| board[square].piece_type == ChessPieces::Pawns);
+    if let Some(square) = back_rank_pawn {
+        return Err(FenError::PawnOnBackRank {
+            square: square as i16,
+        });
+    }
+
+    // both kings are there by now.
+    let opponent = switch_color(&state.color_to_move);
+    let king_square = state.bitboards.pieces(&opponent, ChessPieces::Kings).trailing_zeros() as usize;
+    if is_square_attacked(state, king_square, &state.color_to_move) {
+        return Err(FenError::SideNotToMoveInCheck);
+    }
+
+    let castle = &state.is_able_to_castle;
+    let castling_rights = [
+        ('K', PieceColor::White, true),
+        ('Q', PieceColor::White, false),
+        ('k', PieceColor::Black, true),
+        ('q', PieceColor::Black, false),
+    ];
+    for (right, color, is_kingside) in castling_rights {
+        let rook_file = match castle.rook_file(&color, is_kingside) {
+            Some(rook_file) => rook_file,
+            None => continue,
+        };
+        let home_rank = if color == PieceColor::White { 0 } else { 56 };
+        // the king can start anywhere between the rooks in Chess960, the rook has to be on its side.
+        let king_file = (0..8).find(|file| is_piece(board, home_rank + file, ChessPieces::Kings, color));
+        let is_consistent = king_file.is_some_and(|king_file| (rook_file > king_file) == is_kingside)
+            && is_piece(board, home_rank + rook_file, ChessPieces::Rooks, color);
+        if !is_consistent {
+            return Err(FenError::InconsistentCastling { right });
+        }
+    }
+
+    if let Some(square) = state.en_passant {
+        // (the pawn that pushed, where it started from) relative to the en passant square.
+        let (pawn_offset, start_offset, pawn_color) = match state.color_to_move {
+            PieceColor::White => (-8, 8, PieceColor::Black),
+            _ => (8, -8, PieceColor::White),
+        };
+        let is_plausible = matches!(square / 8, 2 | 5)
+            && (square / 8 == 5) == (state.color_to_move == PieceColor::White)
+            && is_piece(board, (square + pawn_offset) as usize, ChessPieces::Pawns, pawn_color)
+            && board[square as usize].piece_type == ChessPieces::Empty
+            && board[(square + start_offset) as usize].piece_type == ChessPieces::Empty;
+        if !is_plausible {
+            return Err(FenError::ImplausibleEnPassant { square });
+        }
+    }
+
+    Ok(())
+}
+
+fn is_piece(board: &[BoardPiece; 64], square: usize, piece_type: ChessPieces, color: PieceColor) -> bool {
+    board[square].piece_type == piece_type && board[square].piece_color == color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    fn validate(fen: &str) -> Result<(), FenError> {
+        let state = load_fen_state(fen.to_string()).unwrap();
+        validate_fen_state(&state)
+    }
+
+    #[test]
+    fn accepts_real_positions() {
+        assert_eq!(validate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Ok(()));
+        assert_eq!(validate("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"), Ok(()));
+        assert_eq!(validate("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        assert_eq!(
+            validate("8/8/8/8/8/8/8/K7 w - - 0 1"),
+            Err(FenError::WrongKingCount { color: PieceColor::Black, count: 0 })
+        );
+        assert_eq!(validate("P6k/8/8/8/8/8/8/K7 w - - 0 1"), Err(FenError::PawnOnBackRank { square: 56 }));
+        assert_eq!(validate("k7/8/8/8/8/8/8/R6K w - - 0 1"), Err(FenError::SideNotToMoveInCheck));
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/4K3 b q - 0 1"),
+            Err(FenError::InconsistentCastling { right: 'q' })
+        );
+        // a file letter still needs its rook there.
+        assert_eq!(validate("4k3/8/8/8/8/8/8/R3K3 w A - 0 1"), Ok(()));
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/R3K3 w B - 0 1"),
+            Err(FenError::InconsistentCastling { right: 'Q' })
+        );
+        assert_eq!(
+            validate("k7/8/8/8/8/8/8/K7 w - e6 0 1"),
+            Err(FenError::ImplausibleEnPassant { square: 44 })
+        );
+    }
+}
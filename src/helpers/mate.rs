@@ -1,11 +1,6 @@
-use crate::{
-    chess_state::{make_move, unmake_move, ChessState},
-    helpers::checks::detect_check,
-    types::SquaresToEdge,
-    generate_moves,
-    switch_color,
-    Move
-};
+use core::fmt;
+
+use crate::{chess_state::ChessState, BoardPiece, ChessPieces, Move};
 
 pub enum Mate {
     Stalemate,
@@ -13,65 +8,194 @@ pub enum Mate {
     No,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+    // the draws below end the game on their own.
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    // these are claimed draws in real chess, the engine always claims them.
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            GameResult::Ongoing => "Ongoing",
+            GameResult::Checkmate => "Checkmate!",
+            GameResult::Stalemate => "Stalemate!",
+            GameResult::FivefoldRepetition => "Draw by fivefold repetition.",
+            GameResult::SeventyFiveMoveRule => "Draw by the seventy-five move rule.",
+            GameResult::InsufficientMaterial => "Draw by insufficient material.",
+            GameResult::ThreefoldRepetition => "Draw by threefold repetition.",
+            GameResult::FiftyMoveRule => "Draw by the fifty move rule.",
+        };
+        write!(f, "{}", result)
+    }
+}
+
 /**
- * Detects stalemates including fifty-move rule, insufficient material, etc.
+ * Detects checkmates and stalemates.
+ *
+ * With strictly legal moves this is just "is there anything left to play?"
+ * See detect_game_result for the other ways a game can end.
  *
  * https://www.chessprogramming.org/Stalemate#Detecting_Stalemate
  */
-pub fn detect_mate(
-    fen_state: &ChessState,
-    friendly_movements: &Vec<Move>,
-    squares_to_edge: &SquaresToEdge,
-    is_in_check: bool,
-) -> Mate {
-    // if we were generating fully legal moves,
-    //      this would've been very easy.
-    // fully legal moves are extremely hard to generate though
-    //
-    // try every move and if there's at least one legal move,
-    //      it's not a stalemate
-    
-    let mut fen_state = fen_state.clone(); // it won't cost thaat much.
-
-    // somehow include checkmates into this.
-    let trapped = friendly_movements.iter().all(|mov| {
-        match make_move(
-            &mut fen_state.board,
-            &friendly_movements,
-            mov.start_square as u32,
-            mov.target_square as u32,
-        ) {
-            Ok(previous_move) => {
-                let (friendly_piece_locations, _) = generate_moves(
-                    &fen_state.board,
-                    &fen_state.color_to_move,
-                    &fen_state.is_able_to_castle,
-                    squares_to_edge,
-                );
-                let (_, enemy_movements) = generate_moves(
-                    &fen_state.board,
-                    &switch_color(&fen_state.color_to_move),
-                    &fen_state.is_able_to_castle,
-                    squares_to_edge,
-                );
-                let is_in_check = detect_check(&friendly_piece_locations, &enemy_movements);
-                match unmake_move(&mut fen_state.board, previous_move) {
-                    Ok(()) => (),
-                    Err(err) => println!("{}", err),
-                };
-                is_in_check
-            }
-            Err(err) => {
-                println!("{}", err);
-                false
-            }
-        }
-    });
-    if trapped && is_in_check {
+pub fn detect_mate(legal_movements: &[Move], is_in_check: bool) -> Mate {
+    if !legal_movements.is_empty() {
+        Mate::No
+    } else if is_in_check {
         Mate::Checkmate
-    } else if trapped {
+    } else {
         Mate::Stalemate
+    }
+}
+
+/**
+ * Works out whether the game is over, including the fifty/seventy-five move rules,
+ *      three/fivefold repetition and dead positions.
+ *
+ * Checkmate wins over the move rules, a mate on the 75th move still counts.
+ */
+pub fn detect_game_result(
+    state: &ChessState,
+    legal_movements: &[Move],
+    is_in_check: bool,
+) -> GameResult {
+    match detect_mate(legal_movements, is_in_check) {
+        Mate::Checkmate => return GameResult::Checkmate,
+        Mate::Stalemate => return GameResult::Stalemate,
+        Mate::No => (),
+    }
+
+    let repetitions = count_repetitions(state);
+    if repetitions >= 5 {
+        GameResult::FivefoldRepetition
+    } else if state.halfmove_clock >= 150 {
+        GameResult::SeventyFiveMoveRule
+    } else if is_insufficient_material(&state.board) {
+        GameResult::InsufficientMaterial
+    } else if repetitions >= 3 {
+        GameResult::ThreefoldRepetition
+    } else if state.halfmove_clock >= 100 {
+        GameResult::FiftyMoveRule
     } else {
-        Mate::No
+        GameResult::Ongoing
+    }
+}
+
+/**
+ * How many times the current position has come up, counting itself.
+ * Only positions since the last capture or pawn move can repeat, and only every other
+ *      one has the same side to move.
+ */
+pub fn count_repetitions(state: &ChessState) -> usize {
+    let reversible_positions = state.position_history.len().min(state.halfmove_clock as usize);
+    let repeats = state
+        .position_history
+        .iter()
+        .rev()
+        .take(reversible_positions)
+        .skip(1)
+        .step_by(2)
+        .filter(|&&key| key == state.zobrist_key)
+        .count();
+    repeats + 1
+}
+
+/**
+ * Dead positions where neither side can ever mate:
+ * K vs K, K and a single minor piece vs K, and kings with bishops all on one square color.
+ */
+pub fn is_insufficient_material(board: &[BoardPiece; 64]) -> bool {
+    let mut knights = 0;
+    let mut bishops = 0;
+    let mut bishop_square_colors = [false; 2]; // [dark, light]
+
+    for (square, piece) in board.iter().enumerate() {
+        match piece.piece_type {
+            ChessPieces::Pawns | ChessPieces::Rooks | ChessPieces::Queens => return false,
+            ChessPieces::Knights => knights += 1,
+            ChessPieces::Bishops => {
+                bishops += 1;
+                bishop_square_colors[(square / 8 + square % 8) % 2] = true;
+            }
+            ChessPieces::Kings | ChessPieces::Empty => (),
+        }
+    }
+
+    knights + bishops <= 1 || (knights == 0 && !(bishop_square_colors[0] && bishop_square_colors[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::{algebraic_notation_decoder, find_move};
+    use crate::helpers::checks::detect_check;
+    use crate::helpers::fen::encode::load_fen_state;
+    use crate::moves::legal_moves::generate_legal_moves;
+
+    fn result_after(fen: &str, moves: &[&str]) -> GameResult {
+        let mut state = load_fen_state(fen.to_string()).unwrap();
+        for notation in moves {
+            let (start, end, promotion) = algebraic_notation_decoder(notation).unwrap();
+            let legal_moves = generate_legal_moves(&state);
+            state.make_move(find_move(&legal_moves, start, end, promotion).unwrap());
+        }
+        let legal_moves = generate_legal_moves(&state);
+        let is_in_check = detect_check(&state, &state.color_to_move);
+        detect_game_result(&state, &legal_moves, is_in_check)
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    #[test]
+    fn mates() {
+        assert_eq!(result_after("6k1/b7/8/8/5p2/7p/7P/r6K w - - 0 54", &[]), GameResult::Checkmate);
+        assert_eq!(result_after("6k1/b7/8/8/5p2/7p/7P/7K w - - 0 54", &[]), GameResult::Stalemate);
+    }
+
+    #[test]
+    fn repetitions() {
+        assert_eq!(result_after(START, &SHUFFLE), GameResult::Ongoing);
+        assert_eq!(result_after(START, &SHUFFLE.repeat(2)), GameResult::ThreefoldRepetition);
+        assert_eq!(result_after(START, &SHUFFLE.repeat(4)), GameResult::FivefoldRepetition);
+    }
+
+    #[test]
+    fn move_rules() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 99 80";
+        assert_eq!(result_after(fen, &[]), GameResult::Ongoing);
+        assert_eq!(result_after(fen, &["a1a2"]), GameResult::FiftyMoveRule);
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 149 80";
+        assert_eq!(result_after(fen, &["a1a2"]), GameResult::SeventyFiveMoveRule);
+        // a mate on the last move still counts.
+        let fen = "6k1/8/6K1/8/8/8/8/R7 w - - 149 80";
+        assert_eq!(result_after(fen, &["a1a8"]), GameResult::Checkmate);
+    }
+
+    #[test]
+    fn dead_positions() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2N1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1",
+        ] {
+            assert_eq!(result_after(fen, &[]), GameResult::InsufficientMaterial, "{}", fen);
+        }
+        for fen in [
+            "3bk3/8/8/8/8/8/8/3BK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/P7/4K3 w - - 0 1",
+        ] {
+            assert_eq!(result_after(fen, &[]), GameResult::Ongoing, "{}", fen);
+        }
     }
 }
@@ -1,6 +1,11 @@
 pub mod mate;
 pub mod checks;
+pub mod chess960;
 pub mod color;
 pub mod display;
+pub mod epd;
 pub mod fen;
+pub mod pgn;
 pub mod pin;
+pub mod san;
+pub mod zobrist;
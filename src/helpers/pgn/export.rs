@@ -0,0 +1,315 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chess_state::{ChessState, START_POSITION};
+use crate::helpers::fen::decode::save_fen_state;
+use crate::helpers::mate::GameResult;
+use crate::helpers::pgn::import::{PgnGame, PgnMove, SEVEN_TAG_ROSTER};
+use crate::helpers::san::move_to_san;
+use crate::types::{ChessPieces, Move, PieceColor};
+
+// export format keeps movetext lines under 80 characters.
+const MAX_LINE_LENGTH: usize = 79;
+
+/**
+ * A fresh record for a game about to be played from starting_state.
+ * Positions other than the normal start get SetUp and FEN tags, so the game can be replayed.
+ * Chess960 positions also get a Variant tag, otherwise other tools read the castling rights
+ *      as normal chess.
+ */
+pub fn new_game_record(starting_state: &ChessState, white: &str, black: &str) -> PgnGame {
+    let mut tags = vec![
+        ("Event".to_string(), "Casual game".to_string()),
+        ("Site".to_string(), "Cirno CLI".to_string()),
+        ("Date".to_string(), today()),
+        ("Round".to_string(), "-".to_string()),
+        ("White".to_string(), white.to_string()),
+        ("Black".to_string(), black.to_string()),
+    ];
+    if let Ok(fen) = save_fen_state(starting_state) {
+        if fen != START_POSITION {
+            if is_chess960(starting_state) {
+                tags.push(("Variant".to_string(), "Chess960".to_string()));
+            }
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+    }
+    PgnGame {
+        tags,
+        starting_state: starting_state.clone(),
+        moves: Vec::new(),
+        comments: Vec::new(),
+        result: None,
+    }
+}
+
+/**
+ * Whether a position only makes sense as Chess960: castling with a rook that isn't on the
+ *      a or h file, with a king that isn't on the e file, or a full back rank that's been shuffled.
+ */
+fn is_chess960(state: &ChessState) -> bool {
+    const STANDARD_ARRAY: [ChessPieces; 8] = [
+        ChessPieces::Rooks,
+        ChessPieces::Knights,
+        ChessPieces::Bishops,
+        ChessPieces::Queens,
+        ChessPieces::Kings,
+        ChessPieces::Bishops,
+        ChessPieces::Knights,
+        ChessPieces::Rooks,
+    ];
+    let castle = &state.is_able_to_castle;
+    [(PieceColor::White, 0), (PieceColor::Black, 56)].iter().any(|(color, home_rank)| {
+        let back_rank = &state.board[*home_rank..home_rank + 8];
+        let is_odd_rook = [(true, 7), (false, 0)]
+            .iter()
+            .any(|(is_kingside, file)| castle.rook_file(color, *is_kingside).is_some_and(|rook_file| rook_file != *file));
+        let is_odd_king = castle.has_rights(color) && back_rank[4].piece_type != ChessPieces::Kings;
+
+        let mut pieces: Vec<ChessPieces> = back_rank
+            .iter()
+            .filter(|piece| piece.piece_color == *color)
+            .map(|piece| piece.piece_type)
+            .collect();
+        let is_shuffled = pieces.len() == 8 && pieces != STANDARD_ARRAY && {
+            let mut standard = STANDARD_ARRAY;
+            pieces.sort_by_key(|piece| *piece as u8);
+            standard.sort_by_key(|piece| *piece as u8);
+            pieces == standard
+        };
+        is_odd_rook || is_odd_king || is_shuffled
+    })
+}
+
+/**
+ * Adds a move to the record, state is the position before it's played.
+ */
+pub fn record_move(game: &mut PgnGame, state: &ChessState, mov: Move) {
+    game.moves.push(PgnMove {
+        mov,
+        san: move_to_san(state, &mov),
+        nags: Vec::new(),
+        comments: Vec::new(),
+        variations: Vec::new(),
+    });
+}
+
+/**
+ * The PGN result of a finished game, color_to_move is the side that can't move on.
+ */
+pub fn pgn_result(result: GameResult, color_to_move: &PieceColor) -> &'static str {
+    match (result, color_to_move) {
+        (GameResult::Ongoing, _) => "*",
+        (GameResult::Checkmate, PieceColor::White) => "0-1",
+        (GameResult::Checkmate, _) => "1-0",
+        _ => "1/2-1/2",
+    }
+}
+
+/**
+ * Writes a game out in PGN export format: the seven tag roster first ("?" for any that are
+ *      missing), then the other tags, then the movetext wrapped under 80 columns.
+ *
+ * The SAN is written fresh from the moves rather than copied, so it always has the
+ *      disambiguation and check marks.
+ */
+pub fn write_pgn(game: &PgnGame) -> String {
+    let result = game.result.as_deref().unwrap_or("*");
+    let mut pgn = String::new();
+
+    for name in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result,
+            _ => game.tag(name).unwrap_or("?"),
+        };
+        pgn.push_str(&tag_pair(name, value));
+    }
+    for (name, value) in &game.tags {
+        if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+            pgn.push_str(&tag_pair(name, value));
+        }
+    }
+    pgn.push('\n');
+
+    let mut tokens: Vec<String> = game.comments.iter().map(|comment| format!("{{{}}}", comment)).collect();
+    write_line(&game.moves, game.starting_state.clone(), !game.comments.is_empty(), &mut tokens);
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push_str("\n\n");
+    pgn
+}
+
+/**
+ * Black's moves only get a number (like 12...) when something came between them and
+ *      white's move: a comment, a variation or the start of the line.
+ */
+fn write_line(
+    moves: &[PgnMove],
+    mut state: ChessState,
+    mut needs_number: bool,
+    tokens: &mut Vec<String>,
+) {
+    needs_number = needs_number || !moves.is_empty();
+    for pgn_move in moves {
+        match state.color_to_move {
+            PieceColor::Black if needs_number => tokens.push(format!("{}...", state.fullmove_number)),
+            PieceColor::Black => (),
+            _ => tokens.push(format!("{}.", state.fullmove_number)),
+        }
+        tokens.push(move_to_san(&state, &pgn_move.mov));
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        tokens.extend(pgn_move.comments.iter().map(|comment| format!("{{{}}}", comment)));
+        needs_number = !pgn_move.comments.is_empty() || !pgn_move.variations.is_empty();
+
+        for variation in &pgn_move.variations {
+            let mut variation_tokens = Vec::new();
+            write_line(variation, state.clone(), true, &mut variation_tokens);
+            // the parentheses hug the moves inside, like (4... Nf6 5. Nc3).
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.extend(variation_tokens);
+        }
+        state.make_move(pgn_move.mov);
+    }
+}
+
+fn tag_pair(name: &str, value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, escaped)
+}
+
+/**
+ * Today in PGN's YYYY.MM.DD, in UTC.
+ */
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// days since 1970-01-01 to (year, month, day).
+// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::chess960::chess960_position;
+    use crate::helpers::fen::validate::load_valid_fen_state;
+    use crate::helpers::pgn::import::PgnReader;
+    use crate::helpers::san::san_to_move;
+
+    // a short annotated game, the reader's tests have a fuller one.
+    const ANNOTATED: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Date "2024.01.02"]
+[Round "-"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[Annotator "someone"]
+
+{Before anything.} 1. e4 e5 ; the usual
+2. Nf3!? (2. Bc4 Nf6 (2... Bc5 3. Qh5) 3. d3) 2... Nc6 3. Bb5?! 1-0
+"#;
+
+    fn read_game(pgn: &str) -> PgnGame {
+        PgnReader::new(pgn.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn read_write_round_trip() {
+        let game = read_game(ANNOTATED);
+        let written = write_pgn(&game);
+
+        assert!(written.starts_with("[Event \"Casual \\\"blitz\\\"\"]\n[Site \"?\"]\n"));
+        assert!(written.ends_with(
+            "[Result \"1-0\"]\n[Annotator \"someone\"]\n\n\
+             {Before anything.} 1. e4 e5 {the usual} 2. Nf3 $5 (2. Bc4 Nf6 (2... Bc5 3. Qh5)\n\
+             3. d3) 2... Nc6 3. Bb5 $6 1-0\n\n"
+        ));
+        assert!(written.lines().all(|line| line.len() < 80));
+        assert_eq!(read_game(&written), game);
+    }
+
+    #[test]
+    fn records_a_game_from_a_custom_start() {
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 4 30";
+        let mut state = load_valid_fen_state(fen.to_string()).unwrap();
+        let mut game = new_game_record(&state, "Player", "Cirno");
+
+        for san in ["Kh8", "Ra8#"] {
+            let mov = san_to_move(&state, san).unwrap();
+            record_move(&mut game, &state, mov);
+            state.make_move(mov);
+        }
+        game.result = Some(pgn_result(GameResult::Checkmate, &state.color_to_move).to_string());
+
+        let written = write_pgn(&game);
+        assert!(written.contains("[Result \"1-0\"]\n"));
+        assert!(written.contains(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n\n30... Kh8 31. Ra8# 1-0", fen)));
+        assert_eq!(read_game(&written).final_state(), state);
+
+        let start = load_valid_fen_state(START_POSITION.to_string()).unwrap();
+        let fresh = new_game_record(&start, "Player", "Player");
+        assert_eq!(fresh.tag("FEN"), None);
+        assert_eq!(fresh.tag("Variant"), None);
+        assert!(write_pgn(&fresh).ends_with("[Result \"*\"]\n\n*\n\n"));
+    }
+
+    #[test]
+    fn chess960_games_say_so() {
+        let variant = |fen: &str| {
+            let state = load_valid_fen_state(fen.to_string()).unwrap();
+            new_game_record(&state, "Player", "Player").tag("Variant").map(str::to_string)
+        };
+        // a shuffled start, even though KQkq reads fine as normal castling.
+        assert_eq!(variant(&chess960_position(0).unwrap()).as_deref(), Some("Chess960"));
+        // later on, only the rook files give it away.
+        assert_eq!(variant("rr2k3/8/8/8/8/8/8/rR1K2R1 w GB - 0 1").as_deref(), Some("Chess960"));
+        assert_eq!(variant("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), None);
+        assert_eq!(variant("6k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 4 30"), None);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(today().len(), 10);
+    }
+}
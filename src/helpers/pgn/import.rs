@@ -0,0 +1,581 @@
+use std::io::{BufRead, Lines};
+use std::mem;
+
+use crate::chess_state::{ChessState, START_POSITION};
+use crate::error_types::PgnError;
+use crate::helpers::fen::validate::load_valid_fen_state;
+use crate::helpers::san::san_to_move;
+use crate::types::Move;
+
+// the tags every PGN game is supposed to have, in this order.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    pub mov: Move,
+    // as written in the file, without any ! or ? on the end.
+    pub san: String,
+    // numeric annotation glyphs, $1 or a trailing ! is 1, ? is 2 and so on.
+    pub nags: Vec<u8>,
+    // the comments after this move.
+    pub comments: Vec<String>,
+    // alternatives to this move, each played from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    // in the order they were written.
+    pub tags: Vec<(String, String)>,
+    // the start position, or the FEN tag's.
+    pub starting_state: ChessState,
+    // the main line, already checked to be legal.
+    pub moves: Vec<PgnMove>,
+    // comments before the first move.
+    pub comments: Vec<String>,
+    // 1-0, 0-1, 1/2-1/2 or *, None if the movetext just stopped.
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /**
+     * The position at the end of the main line.
+     */
+    pub fn final_state(&self) -> ChessState {
+        let mut state = self.starting_state.clone();
+        for pgn_move in &self.moves {
+            state.make_move(pgn_move.mov);
+        }
+        state
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    MoveNumber,
+    San(String),
+    Nag(u8),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Result(String),
+}
+
+/**
+ * Reads PGN games one at a time, only the game being read is held in memory,
+ *      so files with thousands of games are fine.
+ *
+ * Every move is replayed with the engine's own move generation, an illegal move makes that
+ *      game an error and reading carries on with the next one.
+ *
+ * A game ends at its result (1-0, 0-1, 1/2-1/2 or *), or at the next game's tags
+ *      if the result is missing.
+ *
+ * https://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm
+ */
+pub struct PgnReader<R: BufRead> {
+    lines: Lines<R>,
+    line_number: usize,
+    // tokens of the current line that haven't been handed out.
+    pending: Vec<(usize, Token)>,
+    peeked: Option<Result<(usize, Token), PgnError>>,
+    // a { comment } that runs over several lines: the line it started on and its text so far.
+    open_comment: Option<(usize, String)>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            lines: reader.lines(),
+            line_number: 0,
+            pending: Vec::new(),
+            peeked: None,
+            open_comment: None,
+        }
+    }
+
+    fn peek_token(&mut self) -> Option<&Result<(usize, Token), PgnError>> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_token();
+        }
+        self.peeked.as_ref()
+    }
+
+    fn next_token(&mut self) -> Option<Result<(usize, Token), PgnError>> {
+        self.peeked.take().or_else(|| self.read_token())
+    }
+
+    fn read_token(&mut self) -> Option<Result<(usize, Token), PgnError>> {
+        while self.pending.is_empty() {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Some(Err(PgnError::Io(err.to_string()))),
+                None => {
+                    return self
+                        .open_comment
+                        .take()
+                        .map(|(line, _)| Err(PgnError::UnterminatedComment { line }));
+                }
+            };
+            self.line_number += 1;
+            if let Err(err) = self.tokenize_line(&line) {
+                return Some(Err(err));
+            }
+            // handed out front to back.
+            self.pending.reverse();
+        }
+        self.pending.pop().map(Ok)
+    }
+
+    fn tokenize_line(&mut self, line: &str) -> Result<(), PgnError> {
+        let line_number = self.line_number;
+        let mut rest = line;
+
+        if let Some((start_line, mut text)) = self.open_comment.take() {
+            match rest.find('}') {
+                Some(end) => {
+                    text.push(' ');
+                    text.push_str(&rest[..end]);
+                    self.pending.push((start_line, Token::Comment(text.trim().to_string())));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    text.push(' ');
+                    text.push_str(rest);
+                    self.open_comment = Some((start_line, text));
+                    return Ok(());
+                }
+            }
+        } else if line.starts_with('%') {
+            // an escape line, meant for other software.
+            return Ok(());
+        }
+
+        loop {
+            rest = rest.trim_start();
+            let first = match rest.chars().next() {
+                Some(first) => first,
+                None => return Ok(()),
+            };
+            match first {
+                '{' => match rest.find('}') {
+                    Some(end) => {
+                        self.pending.push((line_number, Token::Comment(rest[1..end].trim().to_string())));
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        self.open_comment = Some((line_number, rest[1..].to_string()));
+                        return Ok(());
+                    }
+                },
+                ';' => {
+                    self.pending.push((line_number, Token::Comment(rest[1..].trim().to_string())));
+                    return Ok(());
+                }
+                '(' | ')' => {
+                    let token = if first == '(' { Token::OpenVariation } else { Token::CloseVariation };
+                    self.pending.push((line_number, token));
+                    rest = &rest[1..];
+                }
+                '[' => {
+                    let (tag, after) = parse_tag(rest).ok_or(PgnError::InvalidTag { line: line_number })?;
+                    self.pending.push((line_number, tag));
+                    rest = after;
+                }
+                _ => {
+                    let end = rest
+                        .find(|ch: char| ch.is_whitespace() || "{}();[$".contains(ch))
+                        .unwrap_or(rest.len());
+                    let end = if end == 0 { nag_end(rest) } else { end };
+                    self.push_word(&rest[..end], line_number)?;
+                    rest = &rest[end..];
+                }
+            }
+        }
+    }
+
+    fn push_word(&mut self, word: &str, line_number: usize) -> Result<(), PgnError> {
+        if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            self.pending.push((line_number, Token::Result(word.to_string())));
+            return Ok(());
+        }
+        if let Some(nag) = word.strip_prefix('$') {
+            let nag = nag.parse().map_err(|_| PgnError::UnexpectedToken {
+                line: line_number,
+                token: word.to_string(),
+            })?;
+            self.pending.push((line_number, Token::Nag(nag)));
+            return Ok(());
+        }
+
+        // 12. or 12... and sometimes written right up against the move, like 12.Nf3
+        let digits = word.len() - word.trim_start_matches(|ch: char| ch.is_ascii_digit()).len();
+        let after_digits = &word[digits..];
+        let san = after_digits.trim_start_matches('.');
+        let san = if digits > 0 && san.len() < after_digits.len() {
+            self.pending.push((line_number, Token::MoveNumber));
+            san
+        } else {
+            word
+        };
+        if san.is_empty() {
+            return Ok(());
+        }
+
+        let annotation_start = san.trim_end_matches(['!', '?']).len();
+        let (san, annotation) = san.split_at(annotation_start);
+        if san.is_empty() {
+            return Err(PgnError::UnexpectedToken {
+                line: line_number,
+                token: word.to_string(),
+            });
+        }
+        self.pending.push((line_number, Token::San(san.to_string())));
+        if let Some(nag) = annotation_nag(annotation) {
+            self.pending.push((line_number, Token::Nag(nag)));
+        }
+        Ok(())
+    }
+
+    /**
+     * The tokens of one game: its tags, then the movetext up to the result.
+     * After an error the rest of the game is still read, so the next one starts in the right place.
+     */
+    fn read_game_tokens(&mut self) -> Option<Result<Vec<(usize, Token)>, PgnError>> {
+        let mut tokens = Vec::new();
+        let mut first_error = None;
+        let mut has_movetext = false;
+        let mut depth = 0;
+
+        loop {
+            let is_next_game = matches!(self.peek_token(), Some(Ok((_, Token::Tag(..)))) if has_movetext);
+            if is_next_game {
+                break;
+            }
+            let (line, token) = match self.next_token() {
+                Some(Ok(token)) => token,
+                Some(Err(err)) => {
+                    // a broken file can't be read any further.
+                    let is_fatal = matches!(err, PgnError::Io(_));
+                    first_error.get_or_insert(err);
+                    if is_fatal {
+                        break;
+                    }
+                    continue;
+                }
+                None => break,
+            };
+            match &token {
+                Token::Tag(..) => (),
+                Token::OpenVariation => depth += 1,
+                Token::CloseVariation => depth -= 1,
+                _ => has_movetext = true,
+            }
+            let is_over = matches!(token, Token::Result(_)) && depth <= 0;
+            tokens.push((line, token));
+            if is_over {
+                break;
+            }
+        }
+
+        match first_error {
+            Some(err) => Some(Err(err)),
+            None if tokens.is_empty() => None,
+            None => Some(Ok(tokens)),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tokens = match self.read_game_tokens()? {
+            Ok(tokens) => tokens,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(build_game(tokens))
+    }
+}
+
+fn build_game(tokens: Vec<(usize, Token)>) -> Result<PgnGame, PgnError> {
+    let mut tags = Vec::new();
+    let mut fen_line = 0;
+    let mut movetext = Vec::new();
+    for (line, token) in tokens {
+        match token {
+            Token::Tag(name, value) => {
+                if name == "FEN" {
+                    fen_line = line;
+                }
+                tags.push((name, value));
+            }
+            token => movetext.push((line, token)),
+        }
+    }
+
+    let fen = tags
+        .iter()
+        .find(|(name, _)| name == "FEN")
+        .map_or(START_POSITION.to_string(), |(_, fen)| fen.clone());
+    let starting_state = load_valid_fen_state(fen)
+        .map_err(|error| PgnError::InvalidFen { line: fen_line, error })?;
+
+    let mut index = 0;
+    let line = Line::read(&movetext, &mut index, starting_state.clone(), false)?;
+    Ok(PgnGame {
+        tags,
+        starting_state,
+        moves: line.moves,
+        comments: line.comments,
+        result: line.result,
+    })
+}
+
+// a main line or a variation.
+struct Line {
+    moves: Vec<PgnMove>,
+    // comments before the first move.
+    comments: Vec<String>,
+    result: Option<String>,
+}
+
+impl Line {
+    /**
+     * Replays the moves from state, recursing into variations.
+     * A variation reads up to and including its ')'.
+     */
+    fn read(
+        tokens: &[(usize, Token)],
+        index: &mut usize,
+        mut state: ChessState,
+        is_variation: bool,
+    ) -> Result<Line, PgnError> {
+        let mut line = Line {
+            moves: Vec::new(),
+            comments: Vec::new(),
+            result: None,
+        };
+        // the position before the last move, where its variations start from.
+        let mut state_before_move: Option<ChessState> = None;
+
+        while let Some((line_number, token)) = tokens.get(*index) {
+            *index += 1;
+            match token {
+                Token::MoveNumber | Token::Tag(..) => (),
+                Token::San(san) => {
+                    let mov = san_to_move(&state, san)
+                        .map_err(|error| PgnError::InvalidMove { line: *line_number, error })?;
+                    state_before_move = Some(state.clone());
+                    state.make_move(mov);
+                    line.moves.push(PgnMove {
+                        mov,
+                        san: san.clone(),
+                        nags: Vec::new(),
+                        comments: Vec::new(),
+                        variations: Vec::new(),
+                    });
+                }
+                Token::Nag(nag) => match line.moves.last_mut() {
+                    Some(last) => last.nags.push(*nag),
+                    None => {
+                        return Err(PgnError::UnexpectedToken {
+                            line: *line_number,
+                            token: format!("${}", nag),
+                        })
+                    }
+                },
+                Token::Comment(comment) => match line.moves.last_mut() {
+                    Some(last) => last.comments.push(comment.clone()),
+                    None => line.comments.push(comment.clone()),
+                },
+                Token::OpenVariation => {
+                    let before = state_before_move
+                        .clone()
+                        .ok_or(PgnError::MisplacedVariation { line: *line_number })?;
+                    let mut variation = Line::read(tokens, index, before, true)?;
+                    if let Some(first) = variation.moves.first_mut() {
+                        // comments leading a variation go with its first move.
+                        first.comments.splice(0..0, mem::take(&mut variation.comments));
+                    }
+                    // there's always a move here, state_before_move is only set after one.
+                    line.moves.last_mut().unwrap().variations.push(variation.moves);
+                }
+                Token::CloseVariation if is_variation => return Ok(line),
+                Token::CloseVariation => return Err(PgnError::UnbalancedVariation { line: *line_number }),
+                Token::Result(result) if !is_variation => {
+                    line.result = Some(result.clone());
+                    return Ok(line);
+                }
+                Token::Result(result) => {
+                    return Err(PgnError::UnexpectedToken {
+                        line: *line_number,
+                        token: result.clone(),
+                    })
+                }
+            }
+        }
+
+        if is_variation {
+            let line_number = tokens.last().map_or(0, |(line_number, _)| *line_number);
+            return Err(PgnError::UnbalancedVariation { line: line_number });
+        }
+        Ok(line)
+    }
+}
+
+/**
+ * [Name "value"], quotes and backslashes in the value are escaped with a backslash.
+ * Returns the tag and what's left of the line.
+ */
+fn parse_tag(text: &str) -> Option<(Token, &str)> {
+    let inside = text.strip_prefix('[')?.trim_start();
+    let name_end = inside.find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))?;
+    let (name, after_name) = inside.split_at(name_end);
+    let mut chars = after_name.trim_start().strip_prefix('"')?.char_indices();
+
+    let mut value = String::new();
+    let value_end = loop {
+        match chars.next()? {
+            (_, '\\') => value.push(chars.next()?.1),
+            (index, '"') => break index,
+            (_, ch) => value.push(ch),
+        }
+    };
+    let after_value = after_name.trim_start()[1 + value_end + 1..].trim_start();
+    let rest = after_value.strip_prefix(']')?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((Token::Tag(name.to_string(), value), rest))
+}
+
+// $ followed by its digits.
+fn nag_end(text: &str) -> usize {
+    1 + text[1..].find(|ch: char| !ch.is_ascii_digit()).unwrap_or(text.len() - 1)
+}
+
+fn annotation_nag(annotation: &str) -> Option<u8> {
+    match annotation {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::algebraic_notation_encoder;
+    use crate::error_types::SanError;
+    use crate::helpers::fen::decode::save_fen_state;
+
+    fn read_games(pgn: &str) -> Vec<Result<PgnGame, PgnError>> {
+        PgnReader::new(pgn.as_bytes()).collect()
+    }
+
+    const ANNOTATED: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Date "2024.01.02"]
+[Round "-"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+[Annotator "someone"]
+
+{Before anything.} 1. e4 e5 2.Nf3 $1 Nc6 ; the usual
+3. Bb5!? (3. Bc4 Bc5 (3... Nf6 {two knights} 4. Ng5) 4. c3) 3... a6 {A long comment
+that goes over
+three lines} 4. Ba4?! Nf6 5. O-O 1-0
+"#;
+
+    #[test]
+    fn reads_tags_comments_nags_and_variations() {
+        let games = read_games(ANNOTATED);
+        assert_eq!(games.len(), 1);
+        let game = games[0].as_ref().unwrap();
+
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.tag("Annotator"), Some("someone"));
+        assert_eq!(game.tags.len(), 8);
+        assert_eq!(game.comments, ["Before anything."]);
+        assert_eq!(game.result.as_deref(), Some("1-0"));
+
+        let sans: Vec<&str> = game.moves.iter().map(|pgn_move| pgn_move.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]);
+        assert_eq!(game.moves[2].nags, [1]);
+        assert_eq!(game.moves[3].comments, ["the usual"]);
+        assert_eq!(game.moves[4].nags, [5]);
+        assert_eq!(game.moves[5].comments, ["A long comment that goes over three lines"]);
+        assert_eq!(game.moves[6].nags, [6]);
+
+        // 3. Bc4 replaces Bb5, with 3... Nf6 nested inside replacing Bc5.
+        let variation = &game.moves[4].variations[0];
+        assert_eq!(algebraic_notation_encoder(&variation[0].mov), "f1c4");
+        assert_eq!(variation.len(), 3);
+        let nested = &variation[1].variations[0];
+        assert_eq!(nested[0].comments, ["two knights"]);
+        assert_eq!(algebraic_notation_encoder(&nested[1].mov), "f3g5");
+
+        assert_eq!(
+            save_fen_state(&game.final_state()).unwrap(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+    }
+
+    #[test]
+    fn streams_several_games_and_skips_bad_ones() {
+        let pgn = format!(
+            "{}\n[Event \"second\"]\n\n1. d4 d5 2. Nf3 Nf6 *\n\n[Event \"bad\"]\n\n1. e4 e5 2. Ke3 Nc6 0-1\n\n\
+             [Event \"from a position\"]\n[SetUp \"1\"]\n[FEN \"6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\"]\n\n1. Ra8# 1-0\n\
+             1. e4 {{no tags and no result}}",
+            ANNOTATED
+        );
+        let games = read_games(&pgn);
+        assert_eq!(games.len(), 5);
+        assert_eq!(games[1].as_ref().unwrap().result.as_deref(), Some("*"));
+        assert_eq!(
+            games[2],
+            Err(PgnError::InvalidMove {
+                line: 21,
+                error: SanError::IllegalMove("Ke3".to_string())
+            })
+        );
+        let from_position = games[3].as_ref().unwrap();
+        assert_eq!(from_position.moves.len(), 1);
+        assert_eq!(from_position.tag("SetUp"), Some("1"));
+        let untagged = games[4].as_ref().unwrap();
+        assert!(untagged.tags.is_empty());
+        assert_eq!(untagged.result, None);
+    }
+
+    #[test]
+    fn reports_broken_movetext() {
+        let first_error = |pgn: &str| read_games(pgn).remove(0).unwrap_err();
+        assert_eq!(first_error("1. e4 (1. d4 d5\n"), PgnError::UnbalancedVariation { line: 1 });
+        assert_eq!(
+            first_error("1. e4 (1. d4 d5 *"),
+            PgnError::UnexpectedToken { line: 1, token: "*".to_string() }
+        );
+        assert_eq!(first_error("1. e4 e5)\n*"), PgnError::UnbalancedVariation { line: 1 });
+        assert_eq!(first_error("(1. d4) 1. e4 *"), PgnError::MisplacedVariation { line: 1 });
+        assert_eq!(first_error("[Event \"x\"\n1. e4 *"), PgnError::InvalidTag { line: 1 });
+        assert_eq!(first_error("1. e4\n\n{never closed\n"), PgnError::UnterminatedComment { line: 3 });
+        assert_eq!(
+            first_error("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*").line(),
+            Some(1)
+        );
+    }
+}
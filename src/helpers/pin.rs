@@ -1,80 +1,73 @@
-use crate::{BoardPiece, ChessPieces, Move, PieceColor, SquaresToEdge};
+use crate::{BoardPiece, ChessPieces, SquaresToEdge};
 
 /**
- * Unimplemented: What direction a piece can go in the pin.
+ * Walks outwards from the king to find the friendly pieces pinned to it.
+ *
+ * Each pinned square comes with the squares it can still move to without exposing the king,
+ *      which is the ray between the king and the pinner, including the pinner.
+ * This is the mailbox version for the old generator, positions have theirs in check_info.
  */
-pub fn find_pinned_pieces_in_board(
+pub fn find_pin_rays(
     board: &[BoardPiece; 64],
-    movement: &Vec<Move>,
+    king_square: usize,
     sqs_to_edge: &SquaresToEdge,
-) -> Vec<(i16, ChessPieces, PieceColor)> {
-    // We could run the diagonal traversal again, or label "pierced" moves
-    //      and compile all of them here.
-
-    let mut pinned_pieces: Vec<(i16, ChessPieces, PieceColor)> = vec![];
-    for moves in movement {
-        if matches!(
-            board[moves.start_square as usize].piece_type,
-            ChessPieces::Rooks | ChessPieces::Queens | ChessPieces::Bishops
-        ) {
-            let mut pinned =
-                find_pinned_pieces_in_square(board, moves.start_square as usize, sqs_to_edge);
-            pinned_pieces.extend(pinned.drain(..));
-        }
-    }
-    pinned_pieces
-}
-
-pub fn find_pinned_pieces_in_square(
-    board: &[BoardPiece; 64],
-    start_square: usize,
-    sqs_to_edge: &SquaresToEdge,
-) -> Vec<(i16, ChessPieces, PieceColor)> {
-    let start_piece = &board[start_square];
+) -> Vec<(i16, u64)> {
+    let king = &board[king_square];
     let direction_offsets: [i16; 8] = [
         8, -8, -1, 1, // Up, Down, Left, Right
         7, -7, 9, -9, // Diagonals
     ];
-    let (start_direction_index, end_direction_index) = match start_piece.piece_type {
-        ChessPieces::Queens => (0, 8),
-        ChessPieces::Bishops => (4, 8),
-        ChessPieces::Rooks => (0, 4),
-        _ => (0, 8),
-    };
-    // (piece direction, ..., ...)
-    //
-    // embed direction_offsets into this.
-    let mut pinned_pieces: Vec<(i16, ChessPieces, PieceColor)> = Vec::new();
+    let mut pin_rays: Vec<(i16, u64)> = Vec::new();
 
-    for direction_index in start_direction_index..end_direction_index {
-        // 8
-        let mut path: Vec<(i16, ChessPieces, PieceColor)> = Vec::with_capacity(16);
-        for n in 0..sqs_to_edge[start_square][direction_index] {
-            // 8
-            let target_square = start_square as i16 + direction_offsets[direction_index] * (n + 1);
-            if let Some(target_piece) = board.get(target_square as usize) {
-                if target_piece.piece_type != ChessPieces::Empty
-                    && target_piece.piece_type != ChessPieces::Kings
-                    && target_piece.piece_color != start_piece.piece_color
-                {
-                    path.push((
-                        direction_offsets[direction_index],
-                        target_piece.piece_type,
-                        target_piece.piece_color,
-                    ));
-                    if path.len() > 1 {
-                        break;
-                    }
-                }
-                if target_piece.piece_type == ChessPieces::Kings
-                    && target_piece.piece_color != start_piece.piece_color
-                {
-                    pinned_pieces.extend(path.drain(..));
+    for (direction_index, direction_offset) in direction_offsets.iter().enumerate() {
+        let mut ray = 0u64;
+        let mut friendly_square: Option<i16> = None;
+        for n in 0..sqs_to_edge[king_square][direction_index] {
+            let target_square = king_square as i16 + direction_offset * (n + 1);
+            let target_piece = &board[target_square as usize];
+            ray |= 1 << target_square;
+            if target_piece.piece_type == ChessPieces::Empty {
+                continue;
+            }
+            if target_piece.piece_color == king.piece_color {
+                // a second friendly piece in the way means nothing is pinned.
+                if friendly_square.is_some() {
                     break;
                 }
+                friendly_square = Some(target_square);
+                continue;
             }
+            let is_pinner = match target_piece.piece_type {
+                ChessPieces::Queens => true,
+                ChessPieces::Rooks => direction_index < 4,
+                ChessPieces::Bishops => direction_index >= 4,
+                _ => false,
+            };
+            if let (true, Some(pinned_square)) = (is_pinner, friendly_square) {
+                pin_rays.push((pinned_square, ray));
+            }
+            break;
         }
     }
 
-    pinned_pieces
+    pin_rays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+    use crate::moves::generate_moves::precompute_squares_to_edge;
+
+    #[test]
+    fn pin_rays_run_from_the_king_to_the_pinner() {
+        let sqs_to_edge = precompute_squares_to_edge();
+        // the e2 rook is pinned by the e7 rook and the d2 knight by the b4 bishop,
+        //      the f2 pawn isn't since the g3 pawn stands behind it.
+        let state = load_fen_state("4k3/4r3/8/8/1b5b/6P1/3NRP2/4K3 w - - 0 1".to_string()).unwrap();
+        let mut pin_rays = find_pin_rays(&state.board, 4, &sqs_to_edge);
+        pin_rays.sort();
+        let file = (12..=52).step_by(8).fold(0, |ray, square| ray | 1u64 << square);
+        assert_eq!(pin_rays, [(11, 1 << 11 | 1 << 18 | 1 << 25), (12, file)]);
+    }
 }
@@ -0,0 +1,311 @@
+use crate::chess_state::{convert_algebraic_snippet, is_kingside_castle, square_to_algebraic, ChessState};
+use crate::error_types::SanError;
+use crate::moves::legal_moves::generate_legal_moves;
+use crate::types::{ChessPieces, Move, MoveType};
+
+/**
+ * Writes a legal move in Standard Algebraic Notation, like Nbd7, exd6, e8=Q+ or O-O-O.
+ * state is the position before the move.
+ *
+ * The piece letter comes first (none for pawns), then just enough of the start square to
+ *      tell it apart from the same kind of piece going to the same square: the file,
+ *      else the rank, else both.
+ *
+ * https://www.chessprogramming.org/Algebraic_Chess_Notation#Standard_Algebraic_Notation_.28SAN.29
+ */
+pub fn move_to_san(state: &ChessState, mov: &Move) -> String {
+    let mut san = if mov.move_type == MoveType::Castle {
+        if is_kingside_castle(mov) { "O-O" } else { "O-O-O" }.to_string()
+    } else {
+        moved_piece_san(state, mov)
+    };
+
+    let mut after = state.clone();
+    after.make_move(*mov);
+    if after.check_info.is_check() {
+        let is_mate = generate_legal_moves(&after).is_empty();
+        san.push(if is_mate { '#' } else { '+' });
+    }
+    san
+}
+
+fn moved_piece_san(state: &ChessState, mov: &Move) -> String {
+    let piece_type = state.board[mov.start_square as usize].piece_type;
+    let is_capture = mov.move_type == MoveType::EnPassant
+        || state.board[mov.target_square as usize].piece_type != ChessPieces::Empty;
+    let start = square_to_algebraic(mov.start_square);
+    let mut san = String::new();
+
+    if piece_type == ChessPieces::Pawns {
+        // pawn captures always name the file they come from.
+        if is_capture {
+            san.push_str(&start[..1]);
+        }
+    } else {
+        san.push(piece_letter(piece_type));
+        let rivals: Vec<Move> = generate_legal_moves(state)
+            .into_iter()
+            .filter(|other| {
+                other.target_square == mov.target_square
+                    && other.start_square != mov.start_square
+                    && state.board[other.start_square as usize].piece_type == piece_type
+            })
+            .collect();
+        if !rivals.is_empty() {
+            let shares_file = rivals.iter().any(|other| other.start_square % 8 == mov.start_square % 8);
+            let shares_rank = rivals.iter().any(|other| other.start_square / 8 == mov.start_square / 8);
+            match (shares_file, shares_rank) {
+                (false, _) => san.push_str(&start[..1]),
+                (true, false) => san.push_str(&start[1..]),
+                (true, true) => san.push_str(&start),
+            }
+        }
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&square_to_algebraic(mov.target_square));
+    if let Some(promotion) = mov.promotion {
+        san.push('=');
+        san.push(piece_letter(promotion));
+    }
+    san
+}
+
+/**
+ * Finds the legal move a SAN string stands for.
+ *
+ * It's a bit forgiving: check marks and annotations (+, #, !, ?) are ignored, castling
+ *      can be written with zeroes and promotions can leave out the '='.
+ * Piece letters have to be uppercase, otherwise b would be both a file and a bishop.
+ */
+pub fn san_to_move(state: &ChessState, san: &str) -> Result<Move, SanError> {
+    let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = generate_legal_moves(state);
+
+    if matches!(trimmed, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let is_kingside = trimmed.len() == 3;
+        return legal_moves
+            .into_iter()
+            .find(|mov| {
+                mov.move_type == MoveType::Castle && is_kingside_castle(mov) == is_kingside
+            })
+            .ok_or_else(|| SanError::IllegalMove(san.to_string()));
+    }
+
+    let pattern = parse_san(trimmed).ok_or_else(|| SanError::InvalidSyntax(san.to_string()))?;
+    let candidates: Vec<Move> = legal_moves
+        .into_iter()
+        .filter(|mov| {
+            mov.target_square == pattern.target_square
+                && mov.move_type != MoveType::Castle
+                && state.board[mov.start_square as usize].piece_type == pattern.piece_type
+                && pattern.from_file.is_none_or(|file| mov.start_square % 8 == file)
+                && pattern.from_rank.is_none_or(|rank| mov.start_square / 8 == rank)
+        })
+        .collect();
+
+    let mut matches = candidates.iter().filter(|mov| mov.promotion == pattern.promotion);
+    match (matches.next(), matches.next()) {
+        (Some(mov), None) => Ok(*mov),
+        (Some(_), Some(_)) => Err(SanError::AmbiguousMove(san.to_string())),
+        (None, _) if pattern.promotion.is_none() && !candidates.is_empty() => {
+            Err(SanError::MissingPromotion(san.to_string()))
+        }
+        (None, _) => Err(SanError::IllegalMove(san.to_string())),
+    }
+}
+
+// what a SAN string says about the move, before looking at the legal moves.
+struct SanPattern {
+    piece_type: ChessPieces,
+    from_file: Option<i16>,
+    from_rank: Option<i16>,
+    target_square: i16,
+    promotion: Option<ChessPieces>,
+}
+
+/**
+ * [piece][from file][from rank][x]<target square>[=promotion]
+ */
+fn parse_san(san: &str) -> Option<SanPattern> {
+    let mut chars: Vec<char> = san.chars().collect();
+
+    let promotion = match chars.last().copied().and_then(letter_piece) {
+        Some(piece) if chars.len() > 2 => {
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            Some(piece)
+        }
+        _ => None,
+    };
+    if chars.len() < 2 {
+        return None;
+    }
+    let target: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    if !matches!(target.as_bytes(), [b'a'..=b'h', b'1'..=b'8']) {
+        return None;
+    }
+    let target_square = convert_algebraic_snippet(&target).ok()? as i16;
+
+    let mut prefix = chars.as_slice();
+    let piece_type = match prefix.first().copied().and_then(letter_piece) {
+        Some(piece) => {
+            prefix = &prefix[1..];
+            piece
+        }
+        None if prefix.first() == Some(&'K') => {
+            prefix = &prefix[1..];
+            ChessPieces::Kings
+        }
+        None => ChessPieces::Pawns,
+    };
+    if let [rest @ .., 'x'] = prefix {
+        prefix = rest;
+    }
+    let (from_file, from_rank) = match prefix {
+        [] => (None, None),
+        [file @ 'a'..='h'] => (Some(file_index(*file)), None),
+        [rank @ '1'..='8'] => (None, Some(rank_index(*rank))),
+        [file @ 'a'..='h', rank @ '1'..='8'] => (Some(file_index(*file)), Some(rank_index(*rank))),
+        _ => return None,
+    };
+    // only pawns promote, and a pawn move names at most its file.
+    if promotion.is_some() && piece_type != ChessPieces::Pawns
+        || piece_type == ChessPieces::Pawns && from_rank.is_some()
+    {
+        return None;
+    }
+
+    Some(SanPattern {
+        piece_type,
+        from_file,
+        from_rank,
+        target_square,
+        promotion,
+    })
+}
+
+fn file_index(file: char) -> i16 {
+    (file as u8 - b'a') as i16
+}
+
+fn rank_index(rank: char) -> i16 {
+    (rank as u8 - b'1') as i16
+}
+
+// the pieces a pawn can promote to, kings are handled on their own.
+fn letter_piece(letter: char) -> Option<ChessPieces> {
+    match letter {
+        'Q' => Some(ChessPieces::Queens),
+        'R' => Some(ChessPieces::Rooks),
+        'B' => Some(ChessPieces::Bishops),
+        'N' => Some(ChessPieces::Knights),
+        _ => None,
+    }
+}
+
+pub fn piece_letter(piece_type: ChessPieces) -> char {
+    match piece_type {
+        ChessPieces::Kings => 'K',
+        ChessPieces::Queens => 'Q',
+        ChessPieces::Rooks => 'R',
+        ChessPieces::Bishops => 'B',
+        ChessPieces::Knights => 'N',
+        ChessPieces::Pawns | ChessPieces::Empty => 'P',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    fn all_san(fen: &str) -> Vec<String> {
+        let state = load_fen_state(fen.to_string()).unwrap();
+        generate_legal_moves(&state)
+            .iter()
+            .map(|mov| move_to_san(&state, mov))
+            .collect()
+    }
+
+    fn parse(fen: &str, san: &str) -> Result<Move, SanError> {
+        let state = load_fen_state(fen.to_string()).unwrap();
+        san_to_move(&state, san)
+    }
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn every_legal_move_round_trips() {
+        let fens = [
+            KIWIPETE,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // three queens that can all reach e4.
+            "8/8/1k6/8/Q6Q/8/8/K6Q w - - 0 1",
+        ];
+        for fen in fens {
+            let state = load_fen_state(fen.to_string()).unwrap();
+            let moves = generate_legal_moves(&state);
+            let mut sans: Vec<String> = Vec::new();
+            for mov in &moves {
+                let san = move_to_san(&state, mov);
+                assert_eq!(san_to_move(&state, &san), Ok(*mov), "{} in {}", san, fen);
+                sans.push(san);
+            }
+            sans.sort();
+            sans.dedup();
+            assert_eq!(sans.len(), moves.len(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn writes_san() {
+        let kiwipete = all_san(KIWIPETE);
+        for san in ["O-O", "O-O-O", "Qxf6", "Nxf7", "Bxa6", "Qxh3", "gxh3", "d6", "Nc4", "Rb1"] {
+            assert!(kiwipete.contains(&san.to_string()), "{} missing from {:?}", san, kiwipete);
+        }
+        // two knights and two rooks that can reach the same square.
+        let knights = all_san("k7/8/8/8/8/5N2/8/KN1R3R w - - 0 1");
+        for san in ["Nbd2", "Nfd2", "Rdf1", "Rhf1", "Rd2"] {
+            assert!(knights.contains(&san.to_string()), "{} missing from {:?}", san, knights);
+        }
+        // the a4 and h4 queens share the rank, the h1 and h4 queens the file.
+        let queens = all_san("8/8/1k6/8/Q6Q/8/8/K6Q w - - 0 1");
+        assert!(queens.contains(&"Qh4e4".to_string()), "{:?}", queens);
+        assert!(queens.contains(&"Q1e4".to_string()));
+        assert!(queens.contains(&"Qae4".to_string()));
+
+        assert!(all_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").contains(&"Ra8#".to_string()));
+        assert!(all_san("4k3/3P4/8/8/8/8/8/4K3 w - - 0 1").contains(&"d8=Q+".to_string()));
+        assert!(all_san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").contains(&"exd6".to_string()));
+    }
+
+    #[test]
+    fn reads_loose_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(parse(start, "Nf3!?").unwrap().target_square, 21);
+        assert_eq!(parse(KIWIPETE, "0-0-0").unwrap().target_square, 2);
+        let promotion = parse("4k3/3P4/8/8/8/8/8/4K3 w - - 0 1", "d8N").unwrap();
+        assert_eq!(promotion.promotion, Some(ChessPieces::Knights));
+    }
+
+    #[test]
+    fn explains_bad_san() {
+        let knights = "k7/8/8/8/8/5N2/8/KN1R3R w - - 0 1";
+        assert_eq!(parse(knights, "Nd2"), Err(SanError::AmbiguousMove("Nd2".to_string())));
+        assert_eq!(parse(knights, "Ne4"), Err(SanError::IllegalMove("Ne4".to_string())));
+        assert_eq!(parse(knights, "O-O"), Err(SanError::IllegalMove("O-O".to_string())));
+        assert_eq!(parse(knights, "Zz9"), Err(SanError::InvalidSyntax("Zz9".to_string())));
+        assert_eq!(parse(knights, "nd2"), Err(SanError::InvalidSyntax("nd2".to_string())));
+        assert_eq!(
+            parse("4k3/3P4/8/8/8/8/8/4K3 w - - 0 1", "d8"),
+            Err(SanError::MissingPromotion("d8".to_string()))
+        );
+    }
+}
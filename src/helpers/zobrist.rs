@@ -0,0 +1,198 @@
+use crate::chess_state::ChessState;
+use crate::types::{BoardPiece, Castle, ChessPieces, PieceColor};
+
+/**
+ * Random numbers XOR'd together to give every position a (nearly) unique 64-bit key.
+ *
+ * https://www.chessprogramming.org/Zobrist_Hashing
+ */
+pub struct ZobristKeys {
+    // [color * 6 + piece][square]
+    pub pieces: [[u64; 64]; 12],
+    pub black_to_move: u64,
+    // white kingside, white queenside, black kingside, black queenside
+    pub castling: [u64; 4],
+    pub en_passant_file: [u64; 8],
+}
+
+// generated at compile time, so every build hashes positions the same way.
+pub const ZOBRIST_KEYS: ZobristKeys = generate_zobrist_keys();
+
+const fn generate_zobrist_keys() -> ZobristKeys {
+    let mut seed = 0x2545_F491_4F6C_DD1D;
+    let mut keys = ZobristKeys {
+        pieces: [[0; 64]; 12],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant_file: [0; 8],
+    };
+
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            seed = split_mix(seed);
+            keys.pieces[piece][square] = seed;
+            square += 1;
+        }
+        piece += 1;
+    }
+    seed = split_mix(seed);
+    keys.black_to_move = seed;
+    let mut index = 0;
+    while index < 4 {
+        seed = split_mix(seed);
+        keys.castling[index] = seed;
+        index += 1;
+    }
+    let mut file = 0;
+    while file < 8 {
+        seed = split_mix(seed);
+        keys.en_passant_file[file] = seed;
+        file += 1;
+    }
+    keys
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+const fn split_mix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn piece_key(piece: BoardPiece, square: usize) -> u64 {
+    let piece_index = match piece.piece_type {
+        ChessPieces::Kings => 0,
+        ChessPieces::Queens => 1,
+        ChessPieces::Rooks => 2,
+        ChessPieces::Bishops => 3,
+        ChessPieces::Knights => 4,
+        ChessPieces::Pawns => 5,
+        ChessPieces::Empty => return 0,
+    };
+    let color_index = match piece.piece_color {
+        PieceColor::White => 0,
+        PieceColor::Black => 6,
+        PieceColor::None => return 0,
+    };
+    ZOBRIST_KEYS.pieces[color_index + piece_index][square]
+}
+
+pub fn castle_key(castle: &Castle) -> u64 {
+    let rights = [
+        castle.white_kingside.is_some(),
+        castle.white_queenside.is_some(),
+        castle.black_kingside.is_some(),
+        castle.black_queenside.is_some(),
+    ];
+    rights
+        .iter()
+        .zip(ZOBRIST_KEYS.castling)
+        .filter(|(is_set, _)| **is_set)
+        .fold(0, |key, (_, castle_key)| key ^ castle_key)
+}
+
+/**
+ * The en passant file only goes into the key when a pawn can actually take en passant,
+ *      otherwise every double push would look like a new position to repetition checks.
+ */
+pub fn en_passant_key(state: &ChessState) -> u64 {
+    let square = match state.en_passant {
+        Some(square) => square,
+        None => return 0,
+    };
+    // the pushed pawn sits one rank past the en passant square, from the side to move's view.
+    let pushed_pawn_square = match state.color_to_move {
+        PieceColor::White => square - 8,
+        _ => square + 8,
+    };
+    let file = square % 8;
+    let can_capture = [-1, 1].iter().any(|file_offset| {
+        let capturer_file = file + file_offset;
+        let capturer_square = pushed_pawn_square + file_offset;
+        (0..8).contains(&capturer_file)
+            && (0..64).contains(&capturer_square)
+            && state.board[capturer_square as usize]
+                == BoardPiece {
+                    piece_type: ChessPieces::Pawns,
+                    piece_color: state.color_to_move,
+                }
+    });
+    if can_capture {
+        ZOBRIST_KEYS.en_passant_file[file as usize]
+    } else {
+        0
+    }
+}
+
+pub fn side_key(color_to_move: &PieceColor) -> u64 {
+    match color_to_move {
+        PieceColor::Black => ZOBRIST_KEYS.black_to_move,
+        _ => 0,
+    }
+}
+
+/**
+ * Hashes the whole position from scratch.
+ * make_move keeps ChessState::zobrist_key up to date incrementally, this is for loading
+ *      positions and double checking the incremental updates.
+ */
+pub fn compute_zobrist_key(state: &ChessState) -> u64 {
+    let pieces = state
+        .board
+        .iter()
+        .enumerate()
+        .fold(0, |key, (square, piece)| key ^ piece_key(*piece, square));
+    pieces
+        ^ side_key(&state.color_to_move)
+        ^ castle_key(&state.is_able_to_castle)
+        ^ en_passant_key(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::{algebraic_notation_decoder, find_move};
+    use crate::helpers::fen::encode::load_fen_state;
+    use crate::moves::legal_moves::generate_legal_moves;
+
+    fn play(fen: &str, moves: &[&str]) -> ChessState {
+        let mut state = load_fen_state(fen.to_string()).unwrap();
+        for notation in moves {
+            let (start, end, promotion) = algebraic_notation_decoder(notation).unwrap();
+            let legal_moves = generate_legal_moves(&state);
+            state.make_move(find_move(&legal_moves, start, end, promotion).unwrap());
+        }
+        state
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let knights_out_and_back = play(START, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(knights_out_and_back.zobrist_key, play(START, &[]).zobrist_key);
+
+        let one_order = play(START, &["e2e3", "e7e6", "d2d3"]);
+        let other_order = play(START, &["d2d3", "e7e6", "e2e3"]);
+        assert_eq!(one_order.zobrist_key, other_order.zobrist_key);
+    }
+
+    #[test]
+    fn side_castling_and_en_passant_change_the_key() {
+        let white = load_fen_state("4k3/8/8/8/8/8/8/4K2R w - - 0 1".to_string()).unwrap();
+        let black = load_fen_state("4k3/8/8/8/8/8/8/4K2R b - - 0 1".to_string()).unwrap();
+        let castle = load_fen_state("4k3/8/8/8/8/8/8/4K2R w K - 0 1".to_string()).unwrap();
+        assert_ne!(white.zobrist_key, black.zobrist_key);
+        assert_ne!(white.zobrist_key, castle.zobrist_key);
+
+        // only a capturable en passant square counts.
+        let capturable = play(START, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        let not_capturable = play(START, &["e2e4", "a7a6", "e4e5", "h7h5"]);
+        assert_eq!(capturable.zobrist_key, compute_zobrist_key(&capturable));
+        assert_ne!(en_passant_key(&capturable), 0);
+        assert_eq!(en_passant_key(&not_capturable), 0);
+    }
+}
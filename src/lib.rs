@@ -0,0 +1,53 @@
+//! Cirno, a chess engine written from scratch.
+//!
+//! A [`ChessState`] holds the position as bitboards, one per piece type and color plus one per
+//!     color, along with the turn, castling rights, en passant square and move clocks.
+//! Rook and bishop attacks are looked up from magic bitboard tables, and the position keeps a
+//!     [`CheckInfo`] (checkers and pins with their rays) up to date, so
+//!     [`generate_legal_moves`] makes legal moves directly without filtering anything afterwards.
+//! Moves are played and taken back with [`ChessState::make_move`] and [`ChessState::unmake_move`].
+//!
+//! A 64 square mailbox where a1 is 0 and h8 is 63 is kept next to the bitboards, it's only there
+//!     to look up what stands on a square, for the display and for FEN.
+//!
+//! ```
+//! use cirno::{generate_legal_moves, load_valid_fen_state, START_POSITION};
+//!
+//! let mut state = load_valid_fen_state(START_POSITION.to_string()).unwrap();
+//! let moves = generate_legal_moves(&state);
+//! assert_eq!(moves.len(), 20);
+//!
+//! let undo = state.make_move(moves[0]);
+//! state.unmake_move(undo);
+//! assert_eq!(cirno::save_fen_state(&state).unwrap(), START_POSITION);
+//! ```
+//!
+//! The `cirno` binary is a thin CLI on top: terminal play, perft, PGN checking, EPD test
+//!     suites, UCI and xboard.
+
+pub mod bitboards;
+pub mod chess_state;
+pub mod error_types;
+pub mod eval;
+pub mod helpers;
+pub mod moves;
+pub mod perft;
+pub mod protocol;
+pub mod search;
+pub mod types;
+
+pub use chess_state::{ChessState, UndoInfo, START_POSITION};
+pub use error_types::{EpdError, FenError, FenField, GameError, PgnError, SanError};
+pub use bitboards::attacks::{attack_map, attackers_to, is_square_attacked};
+pub use bitboards::check_info::{CheckInfo, Pin};
+pub use helpers::checks::detect_check;
+pub use helpers::chess960::chess960_position;
+pub use helpers::epd::{parse_epd, EpdRecord};
+pub use helpers::fen::decode::{save_fen_state, save_shredder_fen_state};
+pub use helpers::fen::validate::load_valid_fen_state;
+pub use helpers::mate::{detect_game_result, GameResult};
+pub use helpers::san::{move_to_san, san_to_move};
+#[doc(hidden)]
+pub use moves::generate_moves::precompute_squares_to_edge;
+pub use moves::legal_moves::generate_legal_moves;
+pub use types::*;
@@ -22,67 +22,89 @@
 // We could have a function that actually
 //      moves the pieces in the chess board using Algebraic Notation.
 
-mod chess_state;
-mod error_types;
-mod helpers;
-mod moves;
-mod types;
+use cirno::chess_state::{algebraic_notation_decoder, algebraic_notation_encoder, find_move};
+use cirno::helpers::epd::parse_epd;
+use cirno::helpers::pgn::export::{new_game_record, pgn_result, record_move, write_pgn};
+use cirno::helpers::pgn::import::{PgnGame, PgnReader};
+use cirno::helpers::{checks, display, fen, san};
+use cirno::perft;
+use cirno::protocol;
+use cirno::search::alpha_beta::{mate_in, search, SearchLimits, SearchResult};
+use cirno::search::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use cirno::{
+    detect_game_result, generate_legal_moves, ChessPieces, ChessState, GameError, GameResult, Move,
+    PieceColor, START_POSITION,
+};
 
-use chess_state::ChessState;
-use helpers::mate::{Mate, detect_mate};
-
-use crate::error_types::GameError;
-
-use crate::chess_state::algebraic_notation_decoder;
-use crate::helpers::*;
-use crate::moves::*;
-use crate::types::*;
-
-use crate::chess_state::{make_move, unmake_move};
-use crate::generate_moves::generate_moves;
-use crate::helpers::checks::unmake_move_based_on_check;
-use crate::helpers::color::switch_color;
-
-use std::io::stdin;
-use std::time::Instant;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stdin, BufReader, Write};
+use std::time::{Duration, Instant};
 
 // DOING:
 //      Board => FEN,           || (Alice, post-movegen)
 //      En Passant, Promotions  || (SMall, movegen)
 //
 // BUGS:
-// Castling movement broken
 // Fix FEN board reversing everything.
 //
 fn main() {
-    let stalemate = "6k1/b7/8/8/5p2/7p/7P/7K w - - 0 54";
-    let checkmate = "6k1/b7/8/8/5p2/7p/7P/r6K w - - 0 54";
-    let normal = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("perft") => return run_perft(&args[1..], false),
+        Some("divide") => return run_perft(&args[1..], true),
+        Some("pgn") => return run_pgn(&args[1..]),
+        Some("epd") => return run_epd(&args[1..]),
+        Some("uci") => return protocol::uci::run_uci(),
+        Some("xboard") => return protocol::xboard::run_xboard(),
+        Some(flag) if flag.starts_with("--") => (),
+        None => (),
+        Some(_) => exit_with_usage(),
+    }
+
+    let _stalemate = "6k1/b7/8/8/5p2/7p/7P/7K w - - 0 54";
+    let _checkmate = "6k1/b7/8/8/5p2/7p/7P/r6K w - - 0 54";
+    let _normal = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let _en_passant = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
     let castling = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
-    let fen = castling;
+    let fen = find_flag_value(&args, "--fen").unwrap_or(castling);
+
+    let engine_color = match find_flag_value(&args, "--engine") {
+        Some("white") => Some(PieceColor::White),
+        Some("black") => Some(PieceColor::Black),
+        Some(_) => exit_with_usage(),
+        None => None,
+    };
+    let limits = parse_search_limits(&args);
+    let hash_mb = match find_flag_value(&args, "--hash").map(str::parse) {
+        Some(Ok(hash_mb)) => hash_mb,
+        Some(Err(_)) => exit_with_usage(),
+        None => DEFAULT_HASH_MB,
+    };
+    let mut table = TranspositionTable::new(hash_mb);
 
-    let squares_to_edge = generate_moves::precompute_squares_to_edge();
     let mut fen_state = load_fen_state(fen.to_string());
 
-    let (_, friendly_movements) = generate_moves(
-        &fen_state.board,
-        &fen_state.color_to_move,
-        &fen_state.is_able_to_castle,
-        &squares_to_edge,
-    );
-    fen_state.color_to_move = switch_color(&fen_state.color_to_move);
+    let player_name = |color| match engine_color {
+        Some(engine_color) if engine_color == color => "Cirno",
+        _ => "Player",
+    };
+    let white = find_flag_value(&args, "--white").unwrap_or(player_name(PieceColor::White));
+    let black = find_flag_value(&args, "--black").unwrap_or(player_name(PieceColor::Black));
+    let mut record = new_game_record(&fen_state, white, black);
+    let pgn_path = find_flag_value(&args, "--pgn").unwrap_or(DEFAULT_PGN_PATH);
 
-    let mut previous_move = None;
     loop {
         let before = Instant::now();
 
         // :D this is the interactable CLI!
-        match game_loop(&mut fen_state, &squares_to_edge, previous_move) {
-            Ok(move_made) => previous_move = move_made,
+        match game_loop(&mut fen_state, engine_color, &limits, &mut table, &mut record) {
+            Ok(()) => (),
             Err(err) => {
                 match err {
                     GameError::End(end) => {
                         println!("Game end: {}", end);
+                        save_game(&record, pgn_path);
                         break;
                     },
                     GameError::UserMoveError(err)
@@ -97,76 +119,339 @@ fn main() {
     }
 }
 
-fn game_loop(
-    fen_state: &mut ChessState,
-    squares_to_edge: &[[i16; 8]; 64],
-    previous_move: Option<(Move, BoardPiece, BoardPiece)>,
-) -> Result<Option<(Move, BoardPiece, BoardPiece)>, GameError> {
-    fen_state.color_to_move = switch_color(&fen_state.color_to_move);
-
-    let (friendly_piece_locations, friendly_movements) = generate_moves(
-        &fen_state.board,
-        &fen_state.color_to_move,
-        &fen_state.is_able_to_castle,
-        squares_to_edge,
-    );
-    let (_, enemy_movements) = generate_moves(
-        &fen_state.board,
-        &switch_color(&fen_state.color_to_move),
-        &fen_state.is_able_to_castle,
-        squares_to_edge,
-    );
+// finished games are added to the end of this file unless --pgn says otherwise.
+const DEFAULT_PGN_PATH: &str = "cirno.pgn";
 
-    let is_in_check = checks::detect_check(&friendly_piece_locations, &enemy_movements);
-    let is_in_mate = detect_mate(&fen_state, &friendly_movements, &squares_to_edge, is_in_check);
-    match is_in_mate {
-        Mate::Stalemate => return Err(GameError::End("Stalemate!".to_string())),
-        Mate::Checkmate => return Err(GameError::End("Checkmate!".to_string())),
-        Mate::No => (),
+const USAGE: &str = "Usage:
+    cirno [--fen <fen>] [--engine white|black] [--depth <n>] [--movetime <ms>] [--hash <mb>]
+          [--white <name>] [--black <name>] [--pgn <file>]
+                                         play in the terminal, optionally against the engine,
+                                         the game is added to <file> (cirno.pgn) when it ends
+    cirno perft <depth> [--fen <fen>]    count leaf nodes for every depth up to <depth>
+    cirno divide <depth> [--fen <fen>]   count leaf nodes under each root move
+    cirno pgn <file>                     replay every game in a PGN file, reporting broken ones
+    cirno epd <file> [--movetime <ms>] [--depth <n>] [--threshold <percent>]
+                                         search every position in an EPD test suite and check
+                                         it against bm/am/dm, fails below <percent> passed
+    cirno uci                            speak UCI on stdin/stdout, for chess GUIs
+    cirno xboard                         speak CECP (xboard/WinBoard) on stdin/stdout";
+
+fn exit_with_usage() -> ! {
+    println!("{}", USAGE);
+    std::process::exit(1);
+}
+
+/**
+ * --depth and --movetime, a second per move if neither is given.
+ */
+fn parse_search_limits(args: &[String]) -> SearchLimits {
+    let parse = |flag| match find_flag_value(args, flag).map(str::parse::<u64>) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(_)) => exit_with_usage(),
+        None => None,
+    };
+    let depth = parse("--depth").map(|depth| depth as u32);
+    let movetime = match (parse("--movetime"), depth) {
+        (Some(movetime), _) => Some(Duration::from_millis(movetime)),
+        (None, Some(_)) => None,
+        (None, None) => Some(Duration::from_secs(1)),
+    };
+    SearchLimits {
+        depth,
+        movetime,
+        ..Default::default()
     }
+}
+
+fn find_flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
 
+/**
+ * perft prints the node count of every depth, divide prints the count under each root move.
+ */
+fn run_perft(args: &[String], is_divide: bool) {
+    let depth: u32 = match args.first().map(|depth| depth.parse()) {
+        Some(Ok(depth)) => depth,
+        _ => exit_with_usage(),
+    };
+    let fen = find_flag_value(args, "--fen").unwrap_or(START_POSITION);
+    let mut fen_state = load_fen_state(fen.to_string());
 
-    match unmake_move_based_on_check(&mut fen_state.board, previous_move, is_in_check) {
-        Ok(()) => (),
+    if is_divide {
+        let before = Instant::now();
+        let root_moves = perft::divide(&mut fen_state, depth);
+        for (mov, nodes) in &root_moves {
+            println!("{}: {}", algebraic_notation_encoder(mov), nodes);
+        }
+        let nodes: u64 = root_moves.iter().map(|(_, nodes)| nodes).sum();
+        println!();
+        println!("Moves: {}", root_moves.len());
+        println!("Nodes: {}", nodes);
+        println!("Elapsed time: {:.2?}", before.elapsed());
+        return;
+    }
+
+    for current_depth in 1..=depth {
+        let before = Instant::now();
+        let nodes = perft::perft(&mut fen_state, current_depth);
+        let elapsed = before.elapsed();
+        let nodes_per_second = nodes as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        println!(
+            "Depth {}: {} nodes ({:.2?}, {:.0} nps)",
+            current_depth, nodes, elapsed, nodes_per_second
+        );
+    }
+}
+
+/**
+ * Checks every game in a PGN file, one line per game.
+ */
+fn run_pgn(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => exit_with_usage(),
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
         Err(err) => {
-            fen_state.color_to_move = switch_color(&fen_state.color_to_move);
-            println!("{}", err)
+            println!("Error! Couldn't open {}: {}", path, err);
+            std::process::exit(1);
         }
+    };
+
+    let mut errors = 0;
+    let mut count = 0;
+    for (index, game) in PgnReader::new(BufReader::new(file)).enumerate() {
+        count += 1;
+        match game {
+            Ok(game) => println!(
+                "Game {}: {} - {}, {} plies, {}",
+                index + 1,
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?"),
+                game.moves.len(),
+                game.result.as_deref().unwrap_or("no result")
+            ),
+            Err(err) => {
+                errors += 1;
+                println!("Game {}: Error! {}", index + 1, err);
+            }
+        }
+    }
+    println!();
+    println!("Games: {}", count);
+    println!("Errors: {}", errors);
+    if errors > 0 {
+        std::process::exit(1);
     }
+}
 
-    let (_, friendly_movements) = generate_moves(
-        &fen_state.board,
-        &fen_state.color_to_move,
-        &fen_state.is_able_to_castle,
-        &squares_to_edge,
-    );
-    println!("{:?}", &fen_state.is_able_to_castle);
-    display::display_chess_tui(&fen_state, &friendly_movements);
+/**
+ * Searches every position in an EPD file and checks the move played against its bm and am,
+ *      and the mate found against its dm.
+ * Exits with 1 when fewer than --threshold percent pass or a line can't be read,
+ *      so a script can tell when a change made the engine worse.
+ */
+fn run_epd(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => exit_with_usage(),
+    };
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            println!("Error! Couldn't open {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+    let limits = parse_search_limits(args);
+    let threshold: f64 = match find_flag_value(args, "--threshold").map(str::parse) {
+        Some(Ok(threshold)) => threshold,
+        Some(Err(_)) => exit_with_usage(),
+        None => 0.0,
+    };
+
+    let mut table = TranspositionTable::new(DEFAULT_HASH_MB);
+    let (mut passed, mut checked, mut errors) = (0, 0, 0);
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let record = match parse_epd(line) {
+            Ok(record) => record,
+            Err(err) => {
+                errors += 1;
+                println!("Line {}: Error! {}", index + 1, err);
+                continue;
+            }
+        };
+        let name = record.id.clone().unwrap_or_else(|| format!("Line {}", index + 1));
+
+        // every position starts from an empty table, earlier ones shouldn't help or hurt.
+        table.clear();
+        let mut state = record.state.clone();
+        let result = search(&mut state, limits.clone(), &mut table);
+        let played = match result.best_move {
+            Some(mov) => mov,
+            None => {
+                println!("{}: no legal moves", name);
+                continue;
+            }
+        };
+        let to_san = |mov: &Move| san::move_to_san(&record.state, mov);
+        let score = match mate_in(result.score) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", result.score),
+        };
+
+        let mut expected = Vec::new();
+        if !record.best_moves.is_empty() {
+            expected.push(format!("bm {}", record.best_moves.iter().map(to_san).collect::<Vec<_>>().join(" ")));
+        }
+        if !record.avoid_moves.is_empty() {
+            expected.push(format!("am {}", record.avoid_moves.iter().map(to_san).collect::<Vec<_>>().join(" ")));
+        }
+        if let Some(moves) = record.direct_mate {
+            expected.push(format!("dm {}", moves));
+        }
+        let verdict = if !record.has_answer() {
+            "no bm, am or dm"
+        } else if record.is_solved_by(&played, mate_in(result.score)) {
+            passed += 1;
+            checked += 1;
+            "pass"
+        } else {
+            checked += 1;
+            "fail"
+        };
+        let expected = if expected.is_empty() { String::new() } else { format!(" ({})", expected.join(", ")) };
+        println!(
+            "{}: {}, played {}{}, depth {} score {}",
+            name,
+            verdict,
+            to_san(&played),
+            expected,
+            result.depth,
+            score
+        );
+    }
+
+    let pass_rate = if checked == 0 { 0.0 } else { passed as f64 * 100.0 / checked as f64 };
+    println!();
+    println!("Passed: {}/{} ({:.1}%)", passed, checked, pass_rate);
+    println!("Errors: {}", errors);
+    if errors > 0 || pass_rate < threshold {
+        if pass_rate < threshold {
+            println!("Below the {}% threshold!", threshold);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn game_loop(
+    fen_state: &mut ChessState,
+    engine_color: Option<PieceColor>,
+    limits: &SearchLimits,
+    table: &mut TranspositionTable,
+    record: &mut PgnGame,
+) -> Result<(), GameError> {
+    let friendly_movements = generate_legal_moves(fen_state);
+    let is_in_check =
+        checks::detect_check(fen_state, &fen_state.color_to_move);
+    match detect_game_result(fen_state, &friendly_movements, is_in_check) {
+        GameResult::Ongoing => (),
+        result => {
+            record.result = Some(pgn_result(result, &fen_state.color_to_move).to_string());
+            return Err(GameError::End(result.to_string()));
+        }
+    }
+
+    if let Ok(fen) = fen::decode::save_fen_state(fen_state) {
+        println!("{}", fen);
+    }
+    display::display_chess_tui(fen_state, &friendly_movements);
+
+    if engine_color == Some(fen_state.color_to_move) {
+        let result = search(fen_state, limits.clone(), table);
+        print_search_result(&result, table);
+        if let Some(mov) = result.best_move {
+            record_move(record, fen_state, mov);
+            fen_state.make_move(mov);
+            println!("Engine moved {}", algebraic_notation_encoder(&mov));
+        }
+        return Ok(());
+    }
 
     let user_input = match get_user_move() {
         Ok(input) => input,
+        Err("End of input.") => return Err(GameError::End("No more input.".to_string())),
         Err(_) => {
-            fen_state.color_to_move = switch_color(&fen_state.color_to_move);
             return Err(GameError::UserMoveError(
                 "Failed to get user move".to_string(),
             ));
         }
     };
 
-    let (start_square_index, end_square_index) = algebraic_notation_decoder(&user_input)
-        .map_err(|_| GameError::NotationDecoderError("Failed to decode notation".to_string()))?;
+    // pgn shows the game so far, pgn <file> writes it out.
+    if let Some(command) = user_input.strip_prefix("pgn") {
+        match command.trim() {
+            "" => print!("{}", write_pgn(record)),
+            path => match fs::write(path, write_pgn(record)) {
+                Ok(()) => println!("Saved the game to {}", path),
+                Err(err) => println!("Error! Couldn't write {}: {}", path, err),
+            },
+        }
+        return Ok(());
+    }
 
-    Ok(make_user_move(
-        fen_state,
-        &friendly_movements,
-        start_square_index,
-        end_square_index,
-        user_input,
-    ))
+    // anything that isn't a coordinate move like e2e4 gets a go as SAN, like Nf3 or O-O.
+    let (start_square_index, end_square_index, promotion) = match algebraic_notation_decoder(&user_input) {
+        Ok(decoded) => decoded,
+        Err(_) => {
+            let mov = san::san_to_move(fen_state, &user_input)
+                .map_err(|err| GameError::NotationDecoderError(err.to_string()))?;
+            record_move(record, fen_state, mov);
+            fen_state.make_move(mov);
+            println!("Moved {}", user_input);
+            return Ok(());
+        }
+    };
+
+    if let Some(mov) = find_user_move(&friendly_movements, start_square_index, end_square_index, promotion) {
+        record_move(record, fen_state, mov);
+        fen_state.make_move(mov);
+        println!("Moved to {}", user_input);
+    }
+    Ok(())
+}
+
+/**
+ * Adds the game to the end of the PGN file, games where nobody moved aren't worth keeping.
+ */
+fn save_game(record: &PgnGame, path: &str) {
+    if record.moves.is_empty() {
+        return;
+    }
+    let saved = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(write_pgn(record).as_bytes()));
+    match saved {
+        Ok(()) => println!("Saved the game to {}", path),
+        Err(err) => println!("Error! Couldn't write {}: {}", path, err),
+    }
 }
 
+/**
+ * Loads and validates a FEN string, the CLI refuses to play out impossible positions.
+ */
 fn load_fen_state(fen: String) -> ChessState {
-    match fen::encode::load_fen_state(fen) {
+    match fen::validate::load_valid_fen_state(fen) {
         Ok(state) => state,
         Err(err) => {
             println!("Error! {}", err);
@@ -175,76 +460,56 @@ fn load_fen_state(fen: String) -> ChessState {
     }
 }
 
-fn make_user_move(
-    fen_state: &mut ChessState,
-    friendly_moves: &Vec<Move>,
+fn find_user_move(
+    friendly_moves: &[Move],
     start_square_index: u32,
     end_square_index: u32,
-    user_input: String,
-) -> Option<(Move, BoardPiece, BoardPiece)> {
-    match make_move(
-        &mut fen_state.board,
+    promotion: Option<ChessPieces>,
+) -> Option<Move> {
+    match find_move(
         friendly_moves,
         start_square_index,
         end_square_index,
+        promotion,
     ) {
-        Ok(move_made) => {
-            let (mov, start_piece, _) = move_made;
-
-            // castling state editing logic
-            match start_piece.piece_color {
-                PieceColor::White => {
-                    if start_piece.piece_type == ChessPieces::Kings {
-                        fen_state.is_able_to_castle.white_kingside = false;
-                        fen_state.is_able_to_castle.white_queenside = false;
-                    }
-                    // assuming rook positions to figure out queenside and kingside.
-                    if start_piece.piece_type == ChessPieces::Rooks {
-                        if mov.start_square == 0 {
-                            fen_state.is_able_to_castle.white_queenside = false;
-                        }
-                        if mov.start_square == 7 {
-                            fen_state.is_able_to_castle.white_kingside = false;
-                        }
-                    }
-                },
-                PieceColor::Black => {
-                    if start_piece.piece_type == ChessPieces::Kings {
-                        fen_state.is_able_to_castle.black_kingside = false;
-                        fen_state.is_able_to_castle.black_queenside = false;
-                    }
-                    // assuming rook positions to figure out queenside and kingside.
-                    if start_piece.piece_type == ChessPieces::Rooks {
-                        if mov.start_square == 56 {
-                            fen_state.is_able_to_castle.white_queenside = false;
-                        }
-                        if mov.start_square == 63 {
-                            fen_state.is_able_to_castle.white_kingside = false;
-                        }
-                    }
-                },
-                PieceColor::None => {}
-            }
-
-            println!("Moved to {}", user_input);
-            Some(move_made)
-        }
+        Ok(mov) => Some(mov),
         Err(err) => {
-            fen_state.color_to_move = switch_color(&fen_state.color_to_move);
             println!("{}", err);
             None
         }
     }
 }
 
+fn print_search_result(result: &SearchResult, table: &TranspositionTable) {
+    let score = match mate_in(result.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let principal_variation: Vec<String> = result
+        .principal_variation
+        .iter()
+        .map(algebraic_notation_encoder)
+        .collect();
+    println!(
+        "depth {} score {} nodes {} hash hits {:.1}% pv {}",
+        result.depth,
+        score,
+        result.nodes,
+        table.stats().hit_rate() * 100.0,
+        principal_variation.join(" ")
+    );
+}
+
 fn get_user_move() -> Result<String, &'static str> {
     let mut input = String::new();
-    println!("Enter your move (e.g. e2e4, f4e2):");
-    stdin().read_line(&mut input).expect("Failed to read line");
+    println!("Enter your move (e.g. e2e4, e7e8q, Nf3, O-O), or pgn [file] for the game so far:");
+    if stdin().read_line(&mut input).expect("Failed to read line") == 0 {
+        return Err("End of input.");
+    }
 
     let input = input.trim();
-    if input.len() != 4 {
-        return Err("Invalid input. Please enter a move like e2e4.");
+    if input.is_empty() {
+        return Err("Invalid input. Please enter a move like e2e4 or Nf3.");
     }
 
     Ok(input.to_string())
@@ -1,6 +1,8 @@
 use crate::{
-    color, king_piece, knight_piece, pawn_piece, sliding_piece, types::SquaresToEdge, BoardPiece,
-    Castle, ChessPieces, Move, PieceColor,
+    helpers::color,
+    moves::{king_piece, knight_piece, pawn_piece, sliding_piece},
+    types::SquaresToEdge,
+    BoardPiece, Castle, ChessPieces, Move, PieceColor,
 };
 use core::cmp::min;
 
@@ -16,8 +18,8 @@ pub fn precompute_squares_to_edge() -> SquaresToEdge {
         for rank in 0..8 {
             // horizontal
             let north = 7 - rank;
-            let south = rank.clone();
-            let west = file.clone();
+            let south = rank;
+            let west = file;
             let east = 7 - file;
 
             let nw = min(north, west);
@@ -41,6 +43,7 @@ pub fn generate_moves(
     board: &[BoardPiece; 64],
     current_player_color: &PieceColor,
     is_able_to_castle: &Castle,
+    en_passant: Option<i16>,
     sqs_to_edge: &SquaresToEdge,
 ) -> (Vec<(ChessPieces, usize)>, Vec<Move>) {
     let mut pieces = Vec::<(ChessPieces, usize)>::new();
@@ -61,6 +64,7 @@ pub fn generate_moves(
                         board,
                         &mut moves,
                         is_able_to_castle,
+                        sqs_to_edge,
                     ),
                     ChessPieces::Knights => {
                         knight_piece::generate_knight_moves(start_square, board, &mut moves)
@@ -77,6 +81,7 @@ pub fn generate_moves(
                         start_square,
                         board,
                         current_player_color,
+                        en_passant,
                         &mut moves,
                     ),
                     _ => (),
@@ -85,5 +90,5 @@ pub fn generate_moves(
         }
     }
 
-    return (pieces, moves);
+    (pieces, moves)
 }
@@ -1,19 +1,20 @@
-use crate::color::is_opponent_color;
-use crate::helpers::color::is_color;
-use crate::types::{BoardPiece, Castle, ChessPieces, Move, MoveType, PieceColor};
+use crate::helpers::color::is_opponent_color;
+use crate::moves::legal_moves::find_attacked_squares;
+use crate::helpers::color::{is_color, switch_color};
+use crate::types::{BoardPiece, Castle, ChessPieces, Move, MoveType, PieceColor, SquaresToEdge};
 
 /**
  * King:
- * - Castling (in progress)
+ * - Castling
  * - Pins
  *
  */
-
 pub fn generate_king_moves(
     start_square: usize,
     board: &[BoardPiece; 64],
     moves: &mut Vec<Move>,
     is_able_to_castle: &Castle,
+    sqs_to_edge: &SquaresToEdge,
 ) {
     let king_moves = [
         (-1, -1),
@@ -27,46 +28,38 @@ pub fn generate_king_moves(
     ];
     let start_piece = board[start_square];
 
-    // this relies on the piece's square
-    //
-    // which means it actually scans the piece's rank and file
-    // rather than the board's rank and file
-    //
-    // not a big deal since this bug will be caught by the FEN castling flag
     let (start_rank, start_file) = (start_square / 8, start_square % 8);
     // rank = horizontal
     // file = vertical
 
-    let first_square_of_rank = start_rank * 8;
-    let last_square_of_rank = (start_rank + 1) * 8;
-    let rank_range = first_square_of_rank..last_square_of_rank;
-    let (queenside, kingside) = check_castle_condition(
-        board.map(|f| f.piece_type)[rank_range].try_into().unwrap(),
-        is_able_to_castle,
-        &start_piece.piece_color,
-    );
-
-    if queenside {
-        moves.push(Move {
-            start_square: start_square as i16,
-            target_square: start_square as i16 - 2,
-            move_type: MoveType::Castle,
-        });
-    }
-    if kingside {
-        moves.push(Move {
-            start_square: start_square as i16,
-            target_square: start_square as i16 + 2,
-            move_type: MoveType::Castle,
-        });
+    // the king has to be on its home rank, the FEN castling flag can't be trusted for that.
+    let home_rank = match start_piece.piece_color {
+        PieceColor::White => 0,
+        PieceColor::Black => 7,
+        PieceColor::None => 8,
+    };
+    if start_rank == home_rank && is_able_to_castle.has_rights(&start_piece.piece_color) {
+        for is_kingside in [false, true] {
+            let rook_file = is_able_to_castle.rook_file(&start_piece.piece_color, is_kingside);
+            if let Some(target_square) =
+                check_castle_condition(board, start_square, rook_file, is_kingside, sqs_to_edge)
+            {
+                moves.push(Move {
+                    start_square: start_square as i16,
+                    target_square: target_square as i16,
+                    move_type: MoveType::Castle,
+                    promotion: None,
+                });
+            }
+        }
     }
 
     for (rank_offset, file_offset) in king_moves {
         let new_rank = start_rank as i16 + rank_offset;
         let new_file = start_file as i16 + file_offset;
 
-        if new_rank >= 0 && new_rank < 8 && new_file >= 0 && new_file < 8 {
-            let target_square = (new_rank * 8 + new_file) as i16;
+        if (0..8).contains(&new_rank) && (0..8).contains(&new_file) {
+            let target_square = new_rank * 8 + new_file;
             let target_piece = board[target_square as usize];
 
             if is_color(&start_piece.piece_color, &target_piece.piece_color) {
@@ -78,6 +71,7 @@ pub fn generate_king_moves(
                     start_square: start_square as i16,
                     target_square,
                     move_type: MoveType::Normal,
+                    promotion: None,
                 });
                 continue;
             }
@@ -86,41 +80,50 @@ pub fn generate_king_moves(
                 start_square: start_square as i16,
                 target_square,
                 move_type: MoveType::Normal,
+                promotion: None,
             });
         }
     }
 }
 
 // is_able_to_castle is a struct that's built from FEN and is modified by a piece moving.
-fn check_castle_condition(ranks: &[ChessPieces; 8], is_able_to_castle: &Castle, current_color: &PieceColor) -> (bool, bool) {
-    let mut queenside = false;
-    let mut kingside = false;
-    match current_color {
-        PieceColor::Black => {
-            if is_able_to_castle.black_queenside {
-                if let [ChessPieces::Rooks, ChessPieces::Empty, ChessPieces::Empty, ChessPieces::Empty, ..] = ranks {
-                    queenside = true;
-                }
-            }
-            if is_able_to_castle.black_kingside {
-                if let [.., ChessPieces::Empty, ChessPieces::Empty, ChessPieces::Rooks] = ranks {
-                    kingside = true;
-                }
-            }
-        },
-        PieceColor::White => {
-            if is_able_to_castle.white_queenside {
-                if let [ChessPieces::Rooks, ChessPieces::Empty, ChessPieces::Empty, ChessPieces::Empty, ..] = ranks {
-                    queenside = true;
-                }
-            }
-            if is_able_to_castle.white_kingside {
-                if let [.., ChessPieces::Empty, ChessPieces::Empty, ChessPieces::Rooks] = ranks {
-                    kingside = true;
-                }
-            }
-        },
-        PieceColor::None => (),
+//
+// Returns where the king lands. Every square the king and rook cross has to be empty apart
+//      from the two of them, and the king can't castle out of, through or into check.
+// The enemy attacks are worked out with the rook off the board, in Chess960 it can be
+//      the only thing blocking an enemy rook on the home rank.
+fn check_castle_condition(
+    board: &[BoardPiece; 64],
+    king_square: usize,
+    rook_file: Option<usize>,
+    is_kingside: bool,
+    sqs_to_edge: &SquaresToEdge,
+) -> Option<usize> {
+    let first_square_of_rank = king_square / 8 * 8;
+    let rook_square = first_square_of_rank + rook_file?;
+    let king = board[king_square];
+    let rook = board[rook_square];
+    if rook.piece_type != ChessPieces::Rooks || rook.piece_color != king.piece_color || (rook_square > king_square) != is_kingside {
+        return None;
+    }
+    let (king_target, rook_target) = if is_kingside {
+        (first_square_of_rank + 6, first_square_of_rank + 5)
+    } else {
+        (first_square_of_rank + 2, first_square_of_rank + 3)
+    };
+
+    let span = |from: usize, to: usize| from.min(to)..=from.max(to);
+    let is_path_empty = span(king_square, king_target)
+        .chain(span(rook_square, rook_target))
+        .all(|square| square == king_square || square == rook_square || board[square].piece_type == ChessPieces::Empty);
+    if !is_path_empty {
+        return None;
     }
-    (queenside, kingside)
+    // only worked out when castling is on the table, it's a whole board scan.
+    let mut without_rook = *board;
+    without_rook[rook_square] = BoardPiece { ..Default::default() };
+    let enemy_attacks = find_attacked_squares(&without_rook, &switch_color(&king.piece_color), sqs_to_edge);
+    span(king_square, king_target)
+        .all(|square| enemy_attacks & 1 << square == 0)
+        .then_some(king_target)
 }
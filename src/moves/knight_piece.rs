@@ -18,7 +18,7 @@ pub fn generate_knight_moves(start_square: usize, board: &[BoardPiece; 64], move
         let new_rank = start_rank as i16 + rank_offset;
         let new_file = start_file as i16 + file_offset;
 
-        if new_rank >= 0 && new_rank < 8 && new_file >= 0 && new_file < 8 {
+        if (0..8).contains(&new_rank) && (0..8).contains(&new_file) {
             let target_square = new_rank * 8 + new_file;
             let target_piece = board[target_square as usize];
 
@@ -31,6 +31,7 @@ pub fn generate_knight_moves(start_square: usize, board: &[BoardPiece; 64], move
                     start_square: start_square as i16,
                     target_square,
                     move_type: MoveType::Normal,
+                    promotion: None,
                 };
                 moves.push(movement);
                 continue;
@@ -38,8 +39,9 @@ pub fn generate_knight_moves(start_square: usize, board: &[BoardPiece; 64], move
 
             let movement = Move {
                 start_square: start_square as i16,
-                target_square: target_square as i16,
+                target_square,
                 move_type: MoveType::Normal,
+                promotion: None,
             };
             moves.push(movement);
         }
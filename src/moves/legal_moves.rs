@@ -0,0 +1,639 @@
+use crate::bitboards::attacks::{
+    attack_map_with_occupancy, attackers_to_with_occupancy, between, king_attacks, knight_attacks, pawn_attacks,
+};
+use crate::bitboards::bitboard::{squares, Bitboards, RANK_1, RANK_8};
+use crate::bitboards::magic::{bishop_attacks, queen_attacks, rook_attacks};
+use crate::chess_state::{en_passant_capture_square, ChessState};
+use crate::moves::generate_moves::generate_moves;
+use crate::moves::pawn_piece::PROMOTION_PIECES;
+use crate::helpers::color::switch_color;
+use crate::helpers::pin::find_pin_rays;
+use crate::types::{BoardPiece, ChessPieces, Move, MoveType, PieceColor, SquaresToEdge};
+
+/**
+ * Generates strictly legal moves for the side to move, straight from the bitboards.
+ *
+ * Before anything is generated it works out:
+ * - king danger squares, every square the enemy hits with our king taken off the board,
+ *   so the king can't step back along a checking ray.
+ * - the check mask, with a single check only captures of the checker or blocks are allowed,
+ *   with a double check only the king can move.
+ * - pins, a pinned piece can only move along the line between the king and the pinner.
+ *
+ * The checkers and pins come from the position's check_info, which make_move keeps up to date.
+ *
+ * En passant can uncover a check along the rank by removing two pieces at once,
+ *      so those few moves are tried on the occupancy instead.
+ */
+pub fn generate_legal_moves(state: &ChessState) -> Vec<Move> {
+    generate_legal(state, false)
+}
+
+/**
+ * The legal captures, en passant captures and promotions, for the quiescence search.
+ */
+pub fn generate_legal_captures(state: &ChessState) -> Vec<Move> {
+    generate_legal(state, true)
+}
+
+/**
+ * The old generator: pseudo-legal moves from scanning the mailbox, then filtered.
+ * It's much slower, but it's kept to check the bitboard generator against and for the benchmark,
+ *      it isn't meant for anything else.
+ */
+#[doc(hidden)]
+pub fn generate_legal_moves_mailbox(state: &ChessState, sqs_to_edge: &SquaresToEdge) -> Vec<Move> {
+    let (pieces, moves) = generate_moves(
+        &state.board,
+        &state.color_to_move,
+        &state.is_able_to_castle,
+        state.en_passant,
+        sqs_to_edge,
+    );
+    filter_legal_moves(state, &pieces, moves, sqs_to_edge)
+}
+
+fn generate_legal(state: &ChessState, captures_only: bool) -> Vec<Move> {
+    let mut moves = Vec::with_capacity(64);
+    let bitboards = &state.bitboards;
+    let us = state.color_to_move;
+    let them = switch_color(&us);
+    let ours = bitboards.color(&us);
+    let theirs = bitboards.color(&them);
+    let occupied = ours | theirs;
+
+    let kings = bitboards.pieces(&us, ChessPieces::Kings);
+    let king_square = kings.trailing_zeros() as usize;
+    let check_info = &state.check_info;
+    let mut check_mask = u64::MAX;
+
+    if kings != 0 {
+        let checkers = check_info.checkers;
+        check_mask = match checkers.count_ones() {
+            0 => u64::MAX,
+            1 => checkers | between(king_square, checkers.trailing_zeros() as usize),
+            _ => 0, // double check, only the king can move.
+        };
+
+        let king_danger = attack_map_with_occupancy(bitboards, &them, occupied ^ kings);
+        let king_targets = king_attacks(king_square) & !ours & !king_danger;
+        let king_targets = if captures_only { king_targets & theirs } else { king_targets };
+        push_moves(&mut moves, king_square, king_targets, MoveType::Normal);
+        if !captures_only && checkers == 0 && state.is_able_to_castle.has_rights(&us) {
+            push_castles(&mut moves, state, king_square, occupied, king_danger);
+        }
+        if check_mask == 0 {
+            return moves;
+        }
+    }
+
+    let targets = check_mask & if captures_only { theirs } else { !ours };
+    for piece_type in [ChessPieces::Knights, ChessPieces::Bishops, ChessPieces::Rooks, ChessPieces::Queens] {
+        for square in squares(bitboards.pieces(&us, piece_type)) {
+            let attacks = match piece_type {
+                ChessPieces::Knights => knight_attacks(square),
+                ChessPieces::Bishops => bishop_attacks(square, occupied),
+                ChessPieces::Rooks => rook_attacks(square, occupied),
+                _ => queen_attacks(square, occupied),
+            };
+            push_moves(&mut moves, square, attacks & targets & check_info.pin_ray(square), MoveType::Normal);
+        }
+    }
+
+    push_pawn_moves(&mut moves, state, check_mask, captures_only);
+    moves
+}
+
+fn push_moves(moves: &mut Vec<Move>, start_square: usize, targets: u64, move_type: MoveType) {
+    for target_square in squares(targets) {
+        moves.push(Move {
+            start_square: start_square as i16,
+            target_square: target_square as i16,
+            move_type,
+            promotion: None,
+        });
+    }
+}
+
+fn push_pawn_moves(
+    moves: &mut Vec<Move>,
+    state: &ChessState,
+    check_mask: u64,
+    captures_only: bool,
+) {
+    let bitboards = &state.bitboards;
+    let us = state.color_to_move;
+    let them = switch_color(&us);
+    let theirs = bitboards.color(&them);
+    let occupied = bitboards.occupied();
+    let (forward, double_push_rank) = match us {
+        PieceColor::White => (8, 1),
+        _ => (-8, 6),
+    };
+
+    for square in squares(bitboards.pieces(&us, ChessPieces::Pawns)) {
+        let allowed = check_mask & state.check_info.pin_ray(square);
+        let push = square as i16 + forward;
+        let mut pushes = 0u64;
+        if (0..64).contains(&push) && occupied & 1 << push == 0 {
+            pushes |= 1 << push;
+            let double_push = push + forward;
+            if square / 8 == double_push_rank && occupied & 1 << double_push == 0 {
+                pushes |= 1 << double_push;
+            }
+        }
+        // with captures_only, pushes onto the last rank still count since they promote.
+        if captures_only {
+            pushes &= RANK_1 | RANK_8;
+        }
+        push_pawn_targets(moves, square, pushes & allowed, MoveType::NoCapture);
+        push_pawn_targets(moves, square, pawn_attacks(&us, square) & theirs & allowed, MoveType::Normal);
+
+        if let Some(en_passant) = state.en_passant {
+            let mov = Move {
+                start_square: square as i16,
+                target_square: en_passant,
+                move_type: MoveType::EnPassant,
+                promotion: None,
+            };
+            if pawn_attacks(&us, square) & 1 << en_passant != 0 && is_en_passant_legal(bitboards, &mov, &us) {
+                moves.push(mov);
+            }
+        }
+    }
+}
+
+/**
+ * Pushes or captures, reaching the last rank splits the move into one promotion per piece.
+ */
+fn push_pawn_targets(moves: &mut Vec<Move>, start_square: usize, targets: u64, move_type: MoveType) {
+    for target_square in squares(targets) {
+        if (RANK_1 | RANK_8) & 1 << target_square == 0 {
+            push_moves(moves, start_square, 1 << target_square, move_type);
+            continue;
+        }
+        for piece in PROMOTION_PIECES {
+            moves.push(Move {
+                start_square: start_square as i16,
+                target_square: target_square as i16,
+                move_type: MoveType::Promotion,
+                promotion: Some(piece),
+            });
+        }
+    }
+}
+
+/**
+ * Plays the capture on the occupancy and looks for anything hitting the king,
+ *      which covers checks, pins and the two pawns leaving a rank at once.
+ */
+fn is_en_passant_legal(bitboards: &Bitboards, mov: &Move, us: &PieceColor) -> bool {
+    let kings = bitboards.pieces(us, ChessPieces::Kings);
+    if kings == 0 {
+        return true;
+    }
+    let captured_bit = 1u64 << en_passant_capture_square(mov);
+    let occupied = bitboards.occupied() ^ (1 << mov.start_square) ^ captured_bit | (1 << mov.target_square);
+    let theirs = bitboards.color(&switch_color(us)) & !captured_bit;
+    attackers_to_with_occupancy(bitboards, kings.trailing_zeros() as usize, occupied) & theirs == 0
+}
+
+/**
+ * The king and the rook it castles with have to be on the home rank, every square either of
+ *      them crosses empty apart from the two of them, and every square the king crosses safe.
+ * Being in check is ruled out before this.
+ *
+ * king_danger already has the king off the board. In Chess960 the rook can be the only thing
+ *      between an enemy rook or queen on the home rank and the square the king lands on,
+ *      so with one of those around it's looked at again with the rook lifted off too.
+ */
+fn push_castles(moves: &mut Vec<Move>, state: &ChessState, king_square: usize, occupied: u64, king_danger: u64) {
+    let us = state.color_to_move;
+    let home_rank = match us {
+        PieceColor::White => 0,
+        PieceColor::Black => 56,
+        PieceColor::None => return,
+    };
+    let bitboards = &state.bitboards;
+    if king_square / 8 != home_rank / 8 {
+        return;
+    }
+    let rooks = bitboards.pieces(&us, ChessPieces::Rooks);
+    let them = switch_color(&us);
+    let rank_sliders = (bitboards.pieces(&them, ChessPieces::Rooks) | bitboards.pieces(&them, ChessPieces::Queens))
+        & RANK_1 << home_rank;
+
+    for is_kingside in [false, true] {
+        let rook_square = match state.is_able_to_castle.rook_file(&us, is_kingside) {
+            Some(rook_file) => home_rank + rook_file,
+            None => continue,
+        };
+        let (king_target, rook_target) = if is_kingside { (home_rank + 6, home_rank + 5) } else { (home_rank + 2, home_rank + 3) };
+        if rooks & 1 << rook_square == 0 || (rook_square > king_square) != is_kingside {
+            continue;
+        }
+        let castlers = 1 << king_square | 1 << rook_square;
+        let king_path = between(king_square, king_target) | 1 << king_square | 1 << king_target;
+        let rook_path = between(rook_square, rook_target) | 1 << rook_square | 1 << rook_target;
+        if occupied & (king_path | rook_path) & !castlers != 0 {
+            continue;
+        }
+        if king_danger & king_path != 0 {
+            continue;
+        }
+        if rank_sliders != 0 && attack_map_with_occupancy(bitboards, &them, occupied ^ castlers) & king_path != 0 {
+            continue;
+        }
+        push_moves(moves, king_square, 1 << king_target, MoveType::Castle);
+    }
+}
+
+fn filter_legal_moves(
+    state: &ChessState,
+    pieces: &[(ChessPieces, usize)],
+    moves: Vec<Move>,
+    sqs_to_edge: &SquaresToEdge,
+) -> Vec<Move> {
+    let king_square = match pieces.iter().find(|(piece, _)| *piece == ChessPieces::Kings) {
+        Some((_, king_square)) => *king_square,
+        None => return moves, // no king, nothing to keep safe.
+    };
+
+    let mut board_without_king = state.board;
+    board_without_king[king_square] = BoardPiece { ..Default::default() };
+    let king_danger_squares = find_attacked_squares(
+        &board_without_king,
+        &switch_color(&state.color_to_move),
+        sqs_to_edge,
+    );
+
+    let (checkers, blocking_squares) = find_checkers(&state.board, king_square, sqs_to_edge);
+    let evasion_squares = match checkers.len() {
+        0 => u64::MAX,
+        1 => blocking_squares,
+        _ => 0, // double check, only the king can move.
+    };
+    let pin_rays = find_pin_rays(&state.board, king_square, sqs_to_edge);
+
+    moves
+        .into_iter()
+        .filter(|mov| {
+            let target_bit = 1u64 << mov.target_square;
+            if mov.start_square as usize == king_square {
+                return king_danger_squares & target_bit == 0;
+            }
+            if mov.move_type == MoveType::EnPassant {
+                return is_mailbox_en_passant_legal(&state.board, mov, king_square, sqs_to_edge);
+            }
+            if evasion_squares & target_bit == 0 {
+                return false;
+            }
+            match pin_rays.iter().find(|(square, _)| *square == mov.start_square) {
+                Some((_, ray)) => ray & target_bit != 0,
+                None => true,
+            }
+        })
+        .collect()
+}
+
+fn is_mailbox_en_passant_legal(
+    board: &[BoardPiece; 64],
+    mov: &Move,
+    king_square: usize,
+    sqs_to_edge: &SquaresToEdge,
+) -> bool {
+    let mut board = *board;
+    board[mov.target_square as usize] = board[mov.start_square as usize];
+    board[mov.start_square as usize] = BoardPiece { ..Default::default() };
+    board[en_passant_capture_square(mov)] = BoardPiece { ..Default::default() };
+    find_checkers(&board, king_square, sqs_to_edge).0.is_empty()
+}
+
+// the mailbox generator's own attack lookups, king_piece's castling uses them as well.
+// they're kept apart from the bitboards on purpose, so the two generators can be checked
+//      against each other.
+const DIRECTION_OFFSETS: [i16; 8] = [
+    8, -8, -1, 1, // Up, Down, Left, Right
+    7, -7, 9, -9, // Diagonals
+];
+// (rank, file)
+const KNIGHT_OFFSETS: [(i16, i16); 8] = [
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+    (1, -2),
+    (2, -1),
+    (2, 1),
+    (1, 2),
+];
+const KING_OFFSETS: [(i16, i16); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/**
+ * Marks every square the attacker color hits as a bit in a u64, square 0 is the lowest bit.
+ *
+ * Unlike the move generator, pawns attack their diagonals even when they're empty
+ *      and sliders attack the first piece they bump into regardless of its color.
+ */
+pub(crate) fn find_attacked_squares(
+    board: &[BoardPiece; 64],
+    attacker_color: &PieceColor,
+    sqs_to_edge: &SquaresToEdge,
+) -> u64 {
+    let mut attacked = 0u64;
+
+    for (start_square, piece) in board.iter().enumerate() {
+        if piece.piece_color != *attacker_color {
+            continue;
+        }
+        let (start_rank, start_file) = ((start_square / 8) as i16, (start_square % 8) as i16);
+        match piece.piece_type {
+            ChessPieces::Pawns => {
+                let forward = if *attacker_color == PieceColor::White { 1 } else { -1 };
+                for file_offset in [-1, 1] {
+                    attacked |= offset_to_bit(start_rank + forward, start_file + file_offset);
+                }
+            }
+            ChessPieces::Knights => {
+                for (rank_offset, file_offset) in KNIGHT_OFFSETS {
+                    attacked |= offset_to_bit(start_rank + rank_offset, start_file + file_offset);
+                }
+            }
+            ChessPieces::Kings => {
+                for (rank_offset, file_offset) in KING_OFFSETS {
+                    attacked |= offset_to_bit(start_rank + rank_offset, start_file + file_offset);
+                }
+            }
+            ChessPieces::Bishops | ChessPieces::Rooks | ChessPieces::Queens => {
+                for direction_index in slider_directions(&piece.piece_type) {
+                    for n in 0..sqs_to_edge[start_square][direction_index] {
+                        let target_square =
+                            start_square as i16 + DIRECTION_OFFSETS[direction_index] * (n + 1);
+                        attacked |= 1 << target_square;
+                        if board[target_square as usize].piece_type != ChessPieces::Empty {
+                            break;
+                        }
+                    }
+                }
+            }
+            ChessPieces::Empty => (),
+        }
+    }
+    attacked
+}
+
+/**
+ * Finds the enemy pieces giving check to the king on king_square.
+ *
+ * Also returns the squares a friendly piece can move to in order to stop a single check,
+ *      which is the checker itself and anything between it and the king.
+ */
+fn find_checkers(
+    board: &[BoardPiece; 64],
+    king_square: usize,
+    sqs_to_edge: &SquaresToEdge,
+) -> (Vec<i16>, u64) {
+    let king = board[king_square];
+    let mut checkers = Vec::new();
+    let mut blocking_squares = 0u64;
+    let (king_rank, king_file) = ((king_square / 8) as i16, (king_square % 8) as i16);
+
+    let is_enemy = |square: i16, piece_type: ChessPieces| {
+        let piece = board[square as usize];
+        piece.piece_type == piece_type
+            && piece.piece_color != king.piece_color
+            && piece.piece_color != PieceColor::None
+    };
+
+    for (rank_offset, file_offset) in KNIGHT_OFFSETS {
+        let bit = offset_to_bit(king_rank + rank_offset, king_file + file_offset);
+        if bit != 0 && is_enemy(bit.trailing_zeros() as i16, ChessPieces::Knights) {
+            checkers.push(bit.trailing_zeros() as i16);
+            blocking_squares |= bit;
+        }
+    }
+
+    // enemy pawns attack the king from the squares diagonally in front of it.
+    let forward = if king.piece_color == PieceColor::White { 1 } else { -1 };
+    for file_offset in [-1, 1] {
+        let bit = offset_to_bit(king_rank + forward, king_file + file_offset);
+        if bit != 0 && is_enemy(bit.trailing_zeros() as i16, ChessPieces::Pawns) {
+            checkers.push(bit.trailing_zeros() as i16);
+            blocking_squares |= bit;
+        }
+    }
+
+    for direction_index in 0..8 {
+        let mut path = 0u64;
+        for n in 0..sqs_to_edge[king_square][direction_index] {
+            let target_square = king_square as i16 + DIRECTION_OFFSETS[direction_index] * (n + 1);
+            path |= 1 << target_square;
+            let target_piece = board[target_square as usize];
+            if target_piece.piece_type == ChessPieces::Empty {
+                continue;
+            }
+            if is_enemy(target_square, ChessPieces::Queens)
+                || (direction_index < 4 && is_enemy(target_square, ChessPieces::Rooks))
+                || (direction_index >= 4 && is_enemy(target_square, ChessPieces::Bishops))
+            {
+                checkers.push(target_square);
+                blocking_squares |= path;
+            }
+            break;
+        }
+    }
+
+    (checkers, blocking_squares)
+}
+
+/**
+ * The range of DIRECTION_OFFSETS a sliding piece moves in.
+ */
+fn slider_directions(piece_type: &ChessPieces) -> std::ops::Range<usize> {
+    match piece_type {
+        ChessPieces::Bishops => 4..8,
+        ChessPieces::Rooks => 0..4,
+        _ => 0..8,
+    }
+}
+
+fn offset_to_bit(rank: i16, file: i16) -> u64 {
+    if (0..8).contains(&rank) && (0..8).contains(&file) {
+        1 << (rank * 8 + file)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::algebraic_notation_encoder;
+    use crate::helpers::fen::encode::load_fen_state;
+    use crate::moves::generate_moves::precompute_squares_to_edge;
+
+    // the legal moves as e2e4 strings, after checking both generators agree on them.
+    fn legal_moves(fen: &str) -> Vec<String> {
+        let sqs_to_edge = precompute_squares_to_edge();
+        let state = load_fen_state(fen.to_string()).unwrap();
+        let notation = |moves: Vec<Move>| {
+            let mut moves: Vec<String> = moves.iter().map(algebraic_notation_encoder).collect();
+            moves.sort();
+            moves
+        };
+        let moves = notation(generate_legal_moves(&state));
+        assert_eq!(moves, notation(generate_legal_moves_mailbox(&state, &sqs_to_edge)), "{}", fen);
+        moves
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_their_ray() {
+        // the e2 rook is pinned by the e7 rook, it can only slide up the file.
+        let moves = legal_moves("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1");
+        for along in ["e2e3", "e2e6", "e2e7"] {
+            assert!(moves.contains(&along.to_string()), "{}", along);
+        }
+        assert!(!moves.contains(&"e2d2".to_string()));
+
+        // a bishop pinned on a file has nowhere to go.
+        let moves = legal_moves("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1");
+        assert!(moves.iter().all(|mov| mov.starts_with("e1")));
+    }
+
+    #[test]
+    fn double_check_only_moves_the_king() {
+        // rook and knight both check, Qxd3 takes one of them but not the other.
+        let moves = legal_moves("4k3/8/8/8/4r3/3n4/8/3QK3 w - - 0 1");
+        assert_eq!(moves, ["e1d2", "e1f1"]);
+    }
+
+    #[test]
+    fn en_passant_cant_uncover_a_check_along_the_rank() {
+        // exd6 would take both pawns off the fifth rank and leave the king facing the rook.
+        let moves = legal_moves("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1");
+        assert!(!moves.contains(&"e5d6".to_string()));
+        assert!(moves.contains(&"e5e6".to_string()));
+
+        let moves = legal_moves("8/8/8/K2pP3/8/8/8/7k w - d6 0 1");
+        assert!(moves.contains(&"e5d6".to_string()));
+    }
+
+    #[test]
+    fn no_castling_through_or_out_of_check() {
+        let castles = |fen| {
+            let moves = legal_moves(fen);
+            (moves.contains(&"e1g1".to_string()), moves.contains(&"e1c1".to_string()))
+        };
+        assert_eq!(castles("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"), (true, true));
+        // f1 is attacked, the king would cross it.
+        assert_eq!(castles("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1"), (false, true));
+        // b1 being attacked doesn't matter, only the rook crosses it.
+        assert_eq!(castles("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"), (true, true));
+        // in check, neither side.
+        assert_eq!(castles("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1"), (false, false));
+    }
+
+    fn is_tactical(state: &ChessState, mov: &Move) -> bool {
+        mov.promotion.is_some()
+            || mov.move_type == MoveType::EnPassant
+            || state.board[mov.target_square as usize].piece_type != ChessPieces::Empty
+    }
+
+    // every position a few plies deep, checking the captures path against the full one.
+    fn assert_captures_match(state: &mut ChessState, depth: u32) {
+        let moves = generate_legal_moves(state);
+        let mut expected: Vec<Move> = moves.iter().copied().filter(|mov| is_tactical(state, mov)).collect();
+        let mut captures = generate_legal_captures(state);
+        let key = |mov: &Move| (mov.start_square, mov.target_square, mov.promotion.map(|piece| piece as u8));
+        expected.sort_by_key(key);
+        captures.sort_by_key(key);
+        assert_eq!(captures, expected);
+
+        if depth == 0 {
+            return;
+        }
+        for mov in moves {
+            let undo = state.make_move(mov);
+            assert_captures_match(state, depth - 1);
+            state.unmake_move(undo);
+        }
+    }
+
+    // the bitboard generator against the mailbox one, move for move.
+    fn assert_generators_match(state: &mut ChessState, depth: u32, sqs_to_edge: &SquaresToEdge) {
+        // in Chess960 the king can castle onto a square it could also step to.
+        let key = |mov: &Move| {
+            let is_castle = mov.move_type == MoveType::Castle;
+            (mov.start_square, mov.target_square, mov.promotion.map(|piece| piece as u8), is_castle)
+        };
+        let mut moves = generate_legal_moves(state);
+        let mut expected = generate_legal_moves_mailbox(state, sqs_to_edge);
+        moves.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(moves, expected, "generators disagree on {:?}", crate::helpers::fen::decode::save_fen_state(state));
+
+        // check_info has to agree with walking the mailbox.
+        let kings = state.bitboards.pieces(&state.color_to_move, ChessPieces::Kings);
+        if kings != 0 {
+            let king_square = kings.trailing_zeros() as usize;
+            let (checkers, _) = find_checkers(&state.board, king_square, sqs_to_edge);
+            assert_eq!(state.check_info.checkers, checkers.iter().fold(0, |bb, square| bb | 1 << square));
+            let pin_rays = find_pin_rays(&state.board, king_square, sqs_to_edge);
+            assert_eq!(state.check_info.pins().len(), pin_rays.len());
+            for (square, ray) in pin_rays {
+                assert_eq!(state.check_info.pin_ray(square as usize), ray);
+            }
+        }
+
+        if depth == 0 {
+            return;
+        }
+        for mov in moves {
+            let undo = state.make_move(mov);
+            assert_generators_match(state, depth - 1, sqs_to_edge);
+            state.unmake_move(undo);
+        }
+    }
+
+    #[test]
+    fn bitboards_match_the_mailbox() {
+        let sqs_to_edge = precompute_squares_to_edge();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Chess960: the king castles onto its rook, and a rook on b1 guards against the one on a1.
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            "rr2k3/8/8/8/8/8/8/rR1K2R1 w GB - 0 1",
+            "1r4kr/8/8/8/8/8/8/4RRK1 b hb - 0 1",
+        ];
+        for fen in fens {
+            let mut state = load_fen_state(fen.to_string()).unwrap();
+            assert_generators_match(&mut state, 2, &sqs_to_edge);
+        }
+    }
+
+    #[test]
+    fn captures_are_the_tactical_legal_moves() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        ];
+        for fen in fens {
+            let mut state = load_fen_state(fen.to_string()).unwrap();
+            assert_captures_match(&mut state, 2);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 pub mod generate_moves;
 pub mod king_piece;
 pub mod knight_piece;
+pub mod legal_moves;
 pub mod pawn_piece;
 pub mod sliding_piece;
@@ -1,12 +1,19 @@
+use crate::helpers::color::is_opponent_color;
 use crate::types::{BoardPiece, ChessPieces, Move, MoveType, PieceColor};
 
-/*
- * Promotion left.
- */
+// queen first, since it's almost always the one you want.
+pub const PROMOTION_PIECES: [ChessPieces; 4] = [
+    ChessPieces::Queens,
+    ChessPieces::Rooks,
+    ChessPieces::Bishops,
+    ChessPieces::Knights,
+];
+
 pub fn generate_pawn_moves(
     start_square: usize,
     board: &[BoardPiece; 64],
     current_player_color: &PieceColor,
+    en_passant: Option<i16>,
     moves: &mut Vec<Move>,
 ) {
     let direction_offsets = match current_player_color {
@@ -22,12 +29,7 @@ pub fn generate_pawn_moves(
         if let Some(target_piece) = target_piece {
             // moving the pieces
             if matches!(direction_offset, -8 | 8) && target_piece.piece_type == ChessPieces::Empty {
-                let movement = Move {
-                    start_square: start_square as i16,
-                    target_square,
-                    move_type: MoveType::NoCapture,
-                };
-                moves.push(movement);
+                push_pawn_move(moves, start_square as i16, target_square, MoveType::NoCapture);
 
                 // Add double move for pawns from initial position
                 let initial_rank = match current_player_color {
@@ -53,6 +55,7 @@ pub fn generate_pawn_moves(
                                 start_square: start_square as i16,
                                 target_square: double_target_square,
                                 move_type: MoveType::NoCapture,
+                                promotion: None,
                             };
                             moves.push(double_movement);
                         }
@@ -62,9 +65,7 @@ pub fn generate_pawn_moves(
             // eating the pieces.
             //
             //
-            if matches!(direction_offset, -7 | 7 | -9 | 9)
-                && target_piece.piece_type != ChessPieces::Empty
-            {
+            if matches!(direction_offset, -7 | 7 | -9 | 9) {
                 // Check if the pawn is not moving off the edge of the board when capturing diagonally
                 let start_file = start_square % 8;
                 let target_file = target_square as usize % 8;
@@ -72,13 +73,45 @@ pub fn generate_pawn_moves(
                     continue;
                 }
 
-                let movement = Move {
-                    start_square: start_square as i16,
-                    target_square,
-                    move_type: MoveType::Normal,
-                };
-                moves.push(movement);
+                // the en passant square is always empty, the pawn being taken is beside us.
+                if en_passant == Some(target_square) {
+                    moves.push(Move {
+                        start_square: start_square as i16,
+                        target_square,
+                        move_type: MoveType::EnPassant,
+                        promotion: None,
+                    });
+                    continue;
+                }
+
+                if is_opponent_color(&target_piece.piece_color, current_player_color) {
+                    push_pawn_move(moves, start_square as i16, target_square, MoveType::Normal);
+                }
             }
         }
     }
 }
+
+/**
+ * Pushes a single step or a capture.
+ * Reaching the last rank splits the move into one promotion per piece.
+ */
+fn push_pawn_move(moves: &mut Vec<Move>, start_square: i16, target_square: i16, move_type: MoveType) {
+    if !matches!(target_square / 8, 0 | 7) {
+        moves.push(Move {
+            start_square,
+            target_square,
+            move_type,
+            promotion: None,
+        });
+        return;
+    }
+    for piece in PROMOTION_PIECES {
+        moves.push(Move {
+            start_square,
+            target_square,
+            move_type: MoveType::Promotion,
+            promotion: Some(piece),
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use crate::color::{is_color, is_opponent_color};
+use crate::helpers::color::{is_color, is_opponent_color};
 use crate::types::{BoardPiece, ChessPieces, Move, MoveType, SquaresToEdge};
 
 pub fn generate_sliding_pieces(
@@ -32,6 +32,7 @@ pub fn generate_sliding_pieces(
                     start_square: start_square as i16,
                     target_square,
                     move_type: MoveType::Normal,
+                    promotion: None,
                 };
                 if is_color(&target_piece.piece_color, &start_piece.piece_color) {
                     break;
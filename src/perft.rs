@@ -0,0 +1,46 @@
+use crate::chess_state::ChessState;
+use crate::moves::legal_moves::generate_legal_moves;
+use crate::types::Move;
+
+/**
+ * Counts the leaf nodes of the move tree down to depth.
+ * Comparing these against the published numbers is how the move generator gets verified.
+ *
+ * https://www.chessprogramming.org/Perft
+ */
+pub fn perft(state: &mut ChessState, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_legal_moves(state);
+    // bulk counting, the moves of the last ply don't need to be played.
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mov in moves {
+        let undo = state.make_move(mov);
+        nodes += perft(state, depth - 1);
+        state.unmake_move(undo);
+    }
+    nodes
+}
+
+/**
+ * Perft split by root move, to narrow down which move a wrong count comes from.
+ */
+pub fn divide(state: &mut ChessState, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    generate_legal_moves(state)
+        .into_iter()
+        .map(|mov| {
+            let undo = state.make_move(mov);
+            let nodes = perft(state, depth - 1);
+            state.unmake_move(undo);
+            (mov, nodes)
+        })
+        .collect()
+}
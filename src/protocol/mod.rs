@@ -0,0 +1,2 @@
+pub mod uci;
+pub mod xboard;
@@ -0,0 +1,325 @@
+use std::io::{stdin, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::chess_state::{
+    algebraic_notation_encoder, chess960_notation_encoder, parse_chess960_move, parse_move, ChessState, START_POSITION,
+};
+use crate::helpers::fen::validate::load_valid_fen_state;
+use crate::search::alpha_beta::{mate_in, search_with_info, SearchLimits, SearchResult};
+use crate::search::time_management::allocate_time;
+use crate::search::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::types::{Move, PieceColor};
+
+const MAX_HASH_MB: usize = 1024;
+
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    is_infinite: bool,
+    handle: JoinHandle<()>,
+}
+
+struct UciEngine {
+    state: ChessState,
+    // shared with the search thread, which holds the lock while it searches.
+    table: Arc<Mutex<TranspositionTable>>,
+    search: Option<RunningSearch>,
+    // UCI_Chess960, castling is written as the king taking its own rook.
+    chess960: bool,
+}
+
+/**
+ * The Universal Chess Interface, so the engine can be loaded into GUIs like Cute Chess or Arena.
+ *
+ * Commands come in on stdin one per line. The search runs on its own thread so "stop" and
+ *      "isready" still get answered while it thinks, it prints an info line for every depth
+ *      and the bestmove when it's done.
+ * When stdin runs out a running search is allowed to finish, so scripts can be piped in.
+ * Setting UCI_Chess960 makes castling go in and out as the king taking its own rook.
+ *
+ * https://backscattering.de/chess/uci/
+ */
+pub fn run_uci() {
+    let mut engine = UciEngine {
+        state: load_valid_fen_state(START_POSITION.to_string()).unwrap(),
+        table: Arc::new(Mutex::new(TranspositionTable::default())),
+        search: None,
+        chess960: false,
+    };
+
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !engine.handle_command(&line) {
+            return;
+        }
+    }
+    engine.wait_for_search();
+}
+
+impl UciEngine {
+    /**
+     * Returns false on quit.
+     */
+    fn handle_command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name Cirno {}", env!("CARGO_PKG_VERSION"));
+                println!("id author the Cirno contributors");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                );
+                println!("option name Clear Hash type button");
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                self.stop_search();
+                self.table.lock().unwrap().clear();
+                self.state = load_valid_fen_state(START_POSITION.to_string()).unwrap();
+            }
+            Some("position") => {
+                self.stop_search();
+                match parse_position(&tokens.collect::<Vec<_>>(), self.chess960) {
+                    Ok(state) => self.state = state,
+                    Err(err) => println!("info string {}", err),
+                }
+            }
+            Some("go") => {
+                self.stop_search();
+                let (limits, is_infinite) = parse_go(&tokens.collect::<Vec<_>>(), &self.state.color_to_move);
+                self.start_search(limits, is_infinite);
+            }
+            Some("stop") => self.stop_search(),
+            Some("setoption") => {
+                self.stop_search();
+                self.set_option(&tokens.collect::<Vec<_>>());
+            }
+            Some("quit") => {
+                self.stop_search();
+                return false;
+            }
+            Some(_) => println!("info string Unknown command: {}", line.trim()),
+            None => (),
+        }
+        true
+    }
+
+    fn start_search(&mut self, mut limits: SearchLimits, is_infinite: bool) {
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(stop.clone());
+
+        let mut state = self.state.clone();
+        let root = self.state.clone();
+        let table = self.table.clone();
+        let chess960 = self.chess960;
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut table = table.lock().unwrap();
+            let result = search_with_info(&mut state, limits, &mut table, |result| {
+                print_info(&root, result, chess960)
+            });
+            // "go infinite" can't answer before it's told to stop, even with a mate in hand.
+            while is_infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            match result.best_move {
+                Some(mov) => println!("bestmove {}", move_notation(&root, &mov, chess960)),
+                None => println!("bestmove 0000"),
+            }
+        });
+
+        self.search = Some(RunningSearch {
+            stop,
+            is_infinite,
+            handle,
+        });
+    }
+
+    /**
+     * Stops the running search (if any) and waits for its bestmove.
+     */
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            search.handle.join().unwrap();
+        }
+    }
+
+    /**
+     * Lets the running search run out on its own, an infinite one is stopped.
+     */
+    fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if search.is_infinite {
+                search.stop.store(true, Ordering::Relaxed);
+            }
+            search.handle.join().unwrap();
+        }
+    }
+
+    // setoption name <name> [value <value>], names can have spaces.
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_index = tokens.iter().position(|token| *token == "value");
+        let name = tokens[..value_index.unwrap_or(tokens.len())]
+            .iter()
+            .skip_while(|token| **token == "name")
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = value_index.map(|index| tokens[index + 1..].join(" "));
+
+        match (name.to_lowercase().as_str(), value) {
+            ("hash", Some(value)) => match value.parse::<usize>() {
+                Ok(size_mb) => self.table.lock().unwrap().resize(size_mb.clamp(1, MAX_HASH_MB)),
+                Err(_) => println!("info string Hash needs a size in MB, got {}", value),
+            },
+            ("clear hash", _) => self.table.lock().unwrap().clear(),
+            ("uci_chess960", Some(value)) => self.chess960 = value.eq_ignore_ascii_case("true"),
+            _ => println!("info string Unknown option: {}", name),
+        }
+    }
+}
+
+/**
+ * position startpos|fen <fen> [moves <move>...]
+ * With UCI_Chess960 on the FEN can be X-FEN or Shredder-FEN and castling comes in as king takes rook.
+ */
+fn parse_position(tokens: &[&str], chess960: bool) -> Result<ChessState, String> {
+    let moves_index = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+    let fen = match tokens.first() {
+        Some(&"startpos") => START_POSITION.to_string(),
+        Some(&"fen") => tokens[1..moves_index].join(" "),
+        _ => return Err("position needs startpos or fen.".to_string()),
+    };
+    let mut state = load_valid_fen_state(fen).map_err(|err| err.to_string())?;
+
+    for notation in tokens.iter().skip(moves_index + 1) {
+        let mov = if chess960 {
+            parse_chess960_move(&state, notation)
+        } else {
+            parse_move(&state, notation)
+        };
+        let mov = mov.map_err(|err| format!("{} {}", notation, err))?;
+        state.make_move(mov);
+    }
+    Ok(state)
+}
+
+/**
+ * go [depth n] [nodes n] [movetime ms] [wtime ms] [btime ms] [winc ms] [binc ms] [movestogo n] [infinite]
+ * Returns the limits and whether it's infinite. Anything it doesn't know is skipped.
+ */
+fn parse_go(tokens: &[&str], color_to_move: &PieceColor) -> (SearchLimits, bool) {
+    let value_of = |name: &str| {
+        tokens
+            .iter()
+            .position(|token| *token == name)
+            .and_then(|index| tokens.get(index + 1))
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    let (time_left, increment) = match color_to_move {
+        PieceColor::Black => (value_of("btime"), value_of("binc")),
+        _ => (value_of("wtime"), value_of("winc")),
+    };
+    let movetime = match (value_of("movetime"), time_left) {
+        (Some(movetime), _) => Some(Duration::from_millis(movetime)),
+        (None, Some(time_left)) => Some(allocate_time(
+            time_left,
+            increment.unwrap_or(0),
+            value_of("movestogo"),
+        )),
+        (None, None) => None,
+    };
+    let limits = SearchLimits {
+        depth: value_of("depth").map(|depth| depth as u32),
+        nodes: value_of("nodes"),
+        movetime,
+        ..Default::default()
+    };
+    (limits, tokens.contains(&"infinite"))
+}
+
+fn move_notation(state: &ChessState, mov: &Move, chess960: bool) -> String {
+    if chess960 {
+        chess960_notation_encoder(state, mov)
+    } else {
+        algebraic_notation_encoder(mov)
+    }
+}
+
+// root is the position searched from, the PV is played out on it to write each move.
+fn print_info(root: &ChessState, result: &SearchResult, chess960: bool) {
+    let score = match mate_in(result.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let elapsed_ms = result.elapsed.as_millis();
+    let nodes_per_second = result.nodes as u128 * 1000 / elapsed_ms.max(1);
+    let mut state = root.clone();
+    let principal_variation: Vec<String> = result
+        .principal_variation
+        .iter()
+        .map(|mov| {
+            let notation = move_notation(&state, mov, chess960);
+            state.make_move(*mov);
+            notation
+        })
+        .collect();
+    println!(
+        "info depth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        result.depth,
+        score,
+        result.nodes,
+        nodes_per_second,
+        result.hashfull,
+        elapsed_ms,
+        principal_variation.join(" ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn go_picks_the_clock_of_the_side_to_move() {
+        let tokens = ["wtime", "30000", "btime", "3000", "movestogo", "1"];
+        let (white, _) = parse_go(&tokens, &PieceColor::White);
+        let (black, _) = parse_go(&tokens, &PieceColor::Black);
+        assert_eq!(white.movetime, Some(Duration::from_millis(29_950)));
+        assert_eq!(black.movetime, Some(Duration::from_millis(2_950)));
+
+        let (limits, is_infinite) = parse_go(&["depth", "6", "infinite"], &PieceColor::White);
+        assert_eq!(limits.depth, Some(6));
+        assert!(is_infinite);
+    }
+
+    #[test]
+    fn chess960_castling_is_king_takes_rook() {
+        // f1g1 castles kingside, the king and rook trade places.
+        let tokens = ["fen", "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB", "w", "GE", "-", "1", "9", "moves", "f1g1"];
+        let state = parse_position(&tokens, true).unwrap();
+        assert_eq!(
+            crate::helpers::fen::decode::save_fen_state(&state).unwrap(),
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRRKB b - - 2 9"
+        );
+        // the rook is on the square the king lands on, so here both ways of writing it agree.
+        assert_eq!(parse_position(&tokens, false), Ok(state));
+
+        let moves = ["startpos", "moves", "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6"];
+        let start = parse_position(&moves, true).unwrap();
+        let castle = parse_chess960_move(&start, "e1h1").unwrap();
+        assert_eq!(move_notation(&start, &castle, false), "e1g1");
+        assert_eq!(move_notation(&start, &castle, true), "e1h1");
+        assert!(parse_chess960_move(&start, "e1g1").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use std::io::{stdin, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::chess_state::{algebraic_notation_encoder, parse_move, ChessState, UndoInfo, START_POSITION};
+use crate::helpers::checks::detect_check;
+use crate::helpers::fen::validate::load_valid_fen_state;
+use crate::helpers::mate::{detect_game_result, GameResult};
+use crate::moves::legal_moves::generate_legal_moves;
+use crate::search::alpha_beta::{mate_in, search_with_info, SearchLimits, SearchResult};
+use crate::search::time_management::allocate_time;
+use crate::search::transposition::TranspositionTable;
+use crate::types::PieceColor;
+
+// xboard shows mate in n as 100000 + n.
+const XBOARD_MATE_SCORE: i32 = 100_000;
+
+enum Event {
+    Command(String),
+    EndOfInput,
+    // the id of the search, so results of a search that got cancelled can be told apart.
+    SearchDone(u64, SearchResult),
+}
+
+struct RunningSearch {
+    id: u64,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+// level <moves per session> <base> <increment>, all in milliseconds.
+#[derive(Default)]
+struct TimeControl {
+    moves_per_session: u64,
+    increment_ms: u64,
+    // set by st, a fixed time for every move.
+    time_per_move: Option<Duration>,
+    // set by sd.
+    depth: Option<u32>,
+    // the engine's clock from "time".
+    time_left_ms: Option<u64>,
+}
+
+struct XboardEngine {
+    state: ChessState,
+    // the moves played since new or setboard, for undo and remove.
+    history: Vec<UndoInfo>,
+    table: Arc<Mutex<TranspositionTable>>,
+    // None in force mode, the engine only moves for this side.
+    engine_color: Option<PieceColor>,
+    is_posting: bool,
+    time_control: TimeControl,
+    search: Option<RunningSearch>,
+    search_count: u64,
+    events: Sender<Event>,
+}
+
+/**
+ * The Chess Engine Communication Protocol (CECP) that xboard, WinBoard and older tournament
+ *      managers speak.
+ *
+ * stdin is read on its own thread and the search runs on another, both hand their results
+ *      to the main loop over one channel. That way "?", "force" and friends are answered
+ *      while the engine is thinking.
+ * When stdin runs out a running search is allowed to finish, so scripts can be piped in.
+ *
+ * https://www.gnu.org/software/xboard/engine-intf.html
+ */
+pub fn run_xboard() {
+    let (events, receiver) = channel();
+    let input_events = events.clone();
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            match line {
+                Ok(line) => input_events.send(Event::Command(line)).unwrap(),
+                Err(_) => break,
+            }
+        }
+        let _ = input_events.send(Event::EndOfInput);
+    });
+
+    let mut engine = XboardEngine {
+        state: load_valid_fen_state(START_POSITION.to_string()).unwrap(),
+        history: Vec::new(),
+        table: Arc::new(Mutex::new(TranspositionTable::default())),
+        engine_color: Some(PieceColor::Black),
+        is_posting: false,
+        time_control: TimeControl::default(),
+        search: None,
+        search_count: 0,
+        events,
+    };
+    engine.run(receiver);
+}
+
+impl XboardEngine {
+    fn run(&mut self, receiver: Receiver<Event>) {
+        let mut is_out_of_input = false;
+        for event in receiver {
+            match event {
+                Event::Command(line) => {
+                    if !self.handle_command(&line) {
+                        self.cancel_search();
+                        return;
+                    }
+                }
+                Event::EndOfInput => is_out_of_input = true,
+                Event::SearchDone(id, result) => {
+                    let is_current = matches!(&self.search, Some(search) if search.id == id);
+                    if is_current {
+                        if let Some(search) = self.search.take() {
+                            search.handle.join().unwrap();
+                        }
+                        self.play_engine_move(result);
+                    }
+                }
+            }
+            if is_out_of_input && self.search.is_none() {
+                return;
+            }
+        }
+    }
+
+    /**
+     * Returns false on quit.
+     */
+    fn handle_command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        let command = match tokens.next() {
+            Some(command) => command,
+            None => return true,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name"
+            | "rating" | "otim" | "ics" => (),
+            "protover" => println!(
+                "feature myname=\"Cirno {}\" setboard=1 usermove=1 ping=1 san=0 colors=0 sigint=0 sigterm=0 analyze=0 done=1",
+                env!("CARGO_PKG_VERSION")
+            ),
+            "ping" => println!("pong {}", arguments.first().unwrap_or(&"")),
+            "new" => {
+                self.cancel_search();
+                self.state = load_valid_fen_state(START_POSITION.to_string()).unwrap();
+                self.history.clear();
+                self.table.lock().unwrap().clear();
+                self.engine_color = Some(PieceColor::Black);
+                self.time_control.time_per_move = None;
+                self.time_control.depth = None;
+            }
+            "setboard" => {
+                self.cancel_search();
+                match load_valid_fen_state(arguments.join(" ")) {
+                    Ok(state) => {
+                        self.state = state;
+                        self.history.clear();
+                    }
+                    Err(err) => println!("tellusererror Illegal position: {}", err),
+                }
+            }
+            "usermove" => {
+                let notation = arguments.first().unwrap_or(&"");
+                match parse_move(&self.state, notation) {
+                    Ok(mov) => {
+                        self.cancel_search();
+                        self.history.push(self.state.make_move(mov));
+                        if !self.report_result() {
+                            self.start_search_if_engine_to_move();
+                        }
+                    }
+                    Err(_) => println!("Illegal move: {}", notation),
+                }
+            }
+            "go" => {
+                self.cancel_search();
+                self.engine_color = Some(self.state.color_to_move);
+                self.start_search_if_engine_to_move();
+            }
+            "force" | "result" => {
+                self.cancel_search();
+                self.engine_color = None;
+            }
+            // move now, the search plays what it has.
+            "?" => {
+                if let Some(search) = &self.search {
+                    search.stop.store(true, Ordering::Relaxed);
+                }
+            }
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "level" => match parse_level(&arguments) {
+                Some((moves_per_session, base_ms, increment_ms)) => {
+                    self.time_control.moves_per_session = moves_per_session;
+                    self.time_control.increment_ms = increment_ms;
+                    self.time_control.time_left_ms = Some(base_ms);
+                    self.time_control.time_per_move = None;
+                }
+                None => println!("Error (bad level): {}", line.trim()),
+            },
+            "st" => match arguments.first().and_then(|seconds| seconds.parse::<f64>().ok()) {
+                Some(seconds) => self.time_control.time_per_move = Some(Duration::from_secs_f64(seconds)),
+                None => println!("Error (bad st): {}", line.trim()),
+            },
+            "sd" => match arguments.first().and_then(|depth| depth.parse().ok()) {
+                Some(depth) => self.time_control.depth = Some(depth),
+                None => println!("Error (bad sd): {}", line.trim()),
+            },
+            // centiseconds.
+            "time" => {
+                if let Some(centiseconds) = arguments.first().and_then(|time| time.parse::<u64>().ok()) {
+                    self.time_control.time_left_ms = Some(centiseconds * 10);
+                }
+            }
+            "post" => self.is_posting = true,
+            "nopost" => self.is_posting = false,
+            "quit" => return false,
+            // protocol version 1 sends bare moves without usermove.
+            _ if parse_move(&self.state, command).is_ok() => {
+                return self.handle_command(&format!("usermove {}", command));
+            }
+            _ => println!("Error (unknown command): {}", command),
+        }
+        true
+    }
+
+    fn start_search_if_engine_to_move(&mut self) {
+        if self.engine_color != Some(self.state.color_to_move) {
+            return;
+        }
+        self.search_count += 1;
+        let id = self.search_count;
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            depth: self.time_control.depth,
+            movetime: Some(self.move_time()),
+            stop: Some(stop.clone()),
+            ..Default::default()
+        };
+
+        let mut state = self.state.clone();
+        let table = self.table.clone();
+        let is_posting = self.is_posting;
+        let events = self.events.clone();
+        let handle = thread::spawn(move || {
+            let mut table = table.lock().unwrap();
+            let result = search_with_info(&mut state, limits, &mut table, |result| {
+                if is_posting {
+                    print_thinking(result);
+                }
+            });
+            // the main loop might be gone after quit, nothing to tell then.
+            let _ = events.send(Event::SearchDone(id, result));
+        });
+        self.search = Some(RunningSearch { id, stop, handle });
+    }
+
+    /**
+     * Stops the running search and throws its move away.
+     */
+    fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            search.handle.join().unwrap();
+        }
+    }
+
+    fn play_engine_move(&mut self, result: SearchResult) {
+        let mov = match result.best_move {
+            Some(mov) => mov,
+            None => return,
+        };
+        self.history.push(self.state.make_move(mov));
+        println!("move {}", algebraic_notation_encoder(&mov));
+        self.report_result();
+    }
+
+    fn take_back(&mut self, count: usize) {
+        self.cancel_search();
+        for _ in 0..count {
+            match self.history.pop() {
+                Some(undo) => self.state.unmake_move(undo),
+                None => break,
+            }
+        }
+    }
+
+    /**
+     * Tells xboard when the game is over, returns true if it is.
+     * The engine drops into force mode until the next new.
+     */
+    fn report_result(&mut self) -> bool {
+        let legal_moves = generate_legal_moves(&self.state);
+        let is_in_check = detect_check(&self.state, &self.state.color_to_move);
+        let result = detect_game_result(&self.state, &legal_moves, is_in_check);
+        let score = match (result, &self.state.color_to_move) {
+            (GameResult::Ongoing, _) => return false,
+            (GameResult::Checkmate, PieceColor::White) => "0-1 {Black mates}".to_string(),
+            (GameResult::Checkmate, _) => "1-0 {White mates}".to_string(),
+            (draw, _) => format!("1/2-1/2 {{{}}}", draw),
+        };
+        println!("{}", score);
+        self.engine_color = None;
+        true
+    }
+
+    fn move_time(&self) -> Duration {
+        let time_control = &self.time_control;
+        if let Some(time_per_move) = time_control.time_per_move {
+            return time_per_move;
+        }
+        match time_control.time_left_ms {
+            Some(time_left_ms) => {
+                // with "level 40 5 0" the clock refills every 40 moves.
+                let moves_to_go = match time_control.moves_per_session {
+                    0 => None,
+                    session => Some(session - (self.history.len() as u64 / 2) % session),
+                };
+                allocate_time(time_left_ms, time_control.increment_ms, moves_to_go)
+            }
+            None => Duration::from_secs(1),
+        }
+    }
+}
+
+/**
+ * level <moves per session> <minutes[:seconds]> <increment seconds>
+ * Returns (moves per session, base time, increment) with the times in milliseconds.
+ */
+fn parse_level(arguments: &[&str]) -> Option<(u64, u64, u64)> {
+    let [moves_per_session, base, increment] = arguments else {
+        return None;
+    };
+    let moves_per_session = moves_per_session.parse().ok()?;
+    let base_ms = match base.split_once(':') {
+        Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60_000 + seconds.parse::<u64>().ok()? * 1000,
+        None => base.parse::<u64>().ok()? * 60_000,
+    };
+    let increment_ms = (increment.parse::<f64>().ok()? * 1000.0) as u64;
+    Some((moves_per_session, base_ms, increment_ms))
+}
+
+// ply score time(centiseconds) nodes pv
+fn print_thinking(result: &SearchResult) {
+    let score = match mate_in(result.score) {
+        Some(moves) if moves > 0 => XBOARD_MATE_SCORE + moves,
+        Some(moves) => -XBOARD_MATE_SCORE + moves,
+        None => result.score,
+    };
+    let principal_variation: Vec<String> = result
+        .principal_variation
+        .iter()
+        .map(algebraic_notation_encoder)
+        .collect();
+    println!(
+        "{} {} {} {} {}",
+        result.depth,
+        score,
+        result.elapsed.as_millis() / 10,
+        result.nodes,
+        principal_variation.join(" ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(parse_level(&["40", "5", "0"]), Some((40, 300_000, 0)));
+        assert_eq!(parse_level(&["0", "2:30", "1.5"]), Some((0, 150_000, 1_500)));
+        assert_eq!(parse_level(&["40", "5"]), None);
+        assert_eq!(parse_level(&["x", "5", "0"]), None);
+    }
+}
@@ -0,0 +1,424 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::chess_state::ChessState;
+use crate::eval::evaluate::evaluate;
+use crate::helpers::mate::{count_repetitions, is_insufficient_material};
+use crate::moves::legal_moves::{generate_legal_captures, generate_legal_moves};
+use crate::search::transposition::{Bound, TranspositionTable};
+use crate::types::{BoardPiece, ChessPieces, Move, MoveType};
+
+// scores are in centipawns from the side to move's point of view.
+// being mated at ply n scores -(MATE_SCORE - n), so shorter mates score higher.
+pub const MATE_SCORE: i32 = 30_000;
+pub const MAX_PLY: u32 = 128;
+const INFINITY: i32 = 32_000;
+// how often (in nodes) the clock and node limit get looked at.
+const LIMIT_CHECK_INTERVAL: u64 = 1024;
+
+/**
+ * When to stop searching, whichever limit is hit first.
+ * With nothing set the search goes to MAX_PLY, so set at least one (or the stop flag).
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    // raised from another thread to stop early, like UCI's "stop".
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    // None when there's no legal move to play.
+    pub best_move: Option<Move>,
+    pub score: i32,
+    // the last depth that was searched all the way through.
+    pub depth: u32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub principal_variation: Vec<Move>,
+    // per mille of the table filled once the depth finished, UCI's hashfull.
+    pub hashfull: u32,
+}
+
+/**
+ * Moves until mate for mate scores, negative when the side to move is getting mated.
+ */
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score > MATE_SCORE - MAX_PLY as i32 {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else if score < -(MATE_SCORE - MAX_PLY as i32) {
+        Some(-(MATE_SCORE + score) / 2)
+    } else {
+        None
+    }
+}
+
+struct Searcher<'a> {
+    table: &'a mut TranspositionTable,
+    limits: SearchLimits,
+    started: Instant,
+    nodes: u64,
+    is_stopped: bool,
+}
+
+/**
+ * Negamax alpha-beta with iterative deepening.
+ *
+ * Every depth from 1 up is searched in turn, the best move of the last one is tried first
+ *      on the next so the cutoffs come early. A depth that gets cut short by the limits
+ *      is thrown away and the last complete one is returned.
+ *
+ * The table is kept between searches, pass the same one in for every move of a game.
+ *
+ * https://www.chessprogramming.org/Alpha-Beta
+ * https://www.chessprogramming.org/Iterative_Deepening
+ */
+pub fn search(
+    state: &mut ChessState,
+    limits: SearchLimits,
+    table: &mut TranspositionTable,
+) -> SearchResult {
+    search_with_info(state, limits, table, |_| ())
+}
+
+/**
+ * Same as search, on_depth gets the result of every depth as it finishes (for UCI's info lines).
+ */
+pub fn search_with_info(
+    state: &mut ChessState,
+    limits: SearchLimits,
+    table: &mut TranspositionTable,
+    mut on_depth: impl FnMut(&SearchResult),
+) -> SearchResult {
+    table.new_search();
+    let mut searcher = Searcher {
+        table,
+        limits,
+        started: Instant::now(),
+        nodes: 0,
+        is_stopped: false,
+    };
+    let mut result = SearchResult {
+        // something to play even if the first depth doesn't finish.
+        best_move: generate_legal_moves(state).first().copied(),
+        ..Default::default()
+    };
+
+    let max_depth = searcher.limits.depth.unwrap_or(MAX_PLY).clamp(1, MAX_PLY);
+    for depth in 1..=max_depth {
+        let mut principal_variation = Vec::new();
+        let score = searcher.negamax(
+            state,
+            depth,
+            0,
+            -INFINITY,
+            INFINITY,
+            &result.principal_variation,
+            &mut principal_variation,
+        );
+        if searcher.is_stopped {
+            break;
+        }
+
+        result = SearchResult {
+            best_move: principal_variation.first().copied(),
+            score,
+            depth,
+            nodes: searcher.nodes,
+            elapsed: searcher.started.elapsed(),
+            principal_variation,
+            hashfull: searcher.table.hashfull(),
+        };
+        on_depth(&result);
+        // no point looking deeper once a forced mate is found.
+        if mate_in(score).is_some() || result.best_move.is_none() {
+            break;
+        }
+    }
+
+    result.nodes = searcher.nodes;
+    result.elapsed = searcher.started.elapsed();
+    result
+}
+
+impl Searcher<'_> {
+    /**
+     * previous_pv is the principal variation of the last iteration, followed move by move
+     *      so each ply tries the last best move first.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        state: &mut ChessState,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+        previous_pv: &[Move],
+        principal_variation: &mut Vec<Move>,
+    ) -> i32 {
+        principal_variation.clear();
+        if depth == 0 {
+            return self.quiescence(state, ply, alpha, beta);
+        }
+        if self.visit_node() {
+            return 0;
+        }
+        if ply > 0 && is_draw(state) {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return evaluate(state);
+        }
+
+        // a deep enough stored score can answer for the whole subtree, the root always
+        //      searches so there's a move to play.
+        let original_alpha = alpha;
+        let mut hash_move = None;
+        if let Some(entry) = self.table.probe(state.zobrist_key, ply) {
+            hash_move = entry.best_move;
+            let is_cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if ply > 0 && entry.depth as u32 >= depth && is_cutoff {
+                return entry.score;
+            }
+        }
+
+        let mut moves = generate_legal_moves(state);
+        if moves.is_empty() {
+            return if state.check_info.is_check() {
+                -(MATE_SCORE - ply as i32)
+            } else {
+                0
+            };
+        }
+        if state.halfmove_clock >= 100 {
+            return 0;
+        }
+
+        let pv_move = previous_pv.first().copied();
+        order_moves(&mut moves, &state.board, pv_move.or(hash_move));
+
+        let mut child_pv = Vec::new();
+        for mov in moves {
+            let child_previous_pv = match pv_move {
+                Some(pv_move) if pv_move == mov => &previous_pv[1..],
+                _ => &[],
+            };
+            let undo = state.make_move(mov);
+            let score = -self.negamax(
+                state,
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                child_previous_pv,
+                &mut child_pv,
+            );
+            state.unmake_move(undo);
+            if self.is_stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                principal_variation.clear();
+                principal_variation.push(mov);
+                principal_variation.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        let bound = if alpha >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        let best_move = principal_variation.first().copied();
+        self.table.store(state.zobrist_key, depth, alpha, bound, best_move, ply);
+        alpha
+    }
+
+    /**
+     * Searches captures and promotions until the position is quiet, so a leaf isn't scored
+     *      in the middle of an exchange.
+     *
+     * The side to move can "stand pat" on the static evaluation instead of capturing,
+     *      since it's not forced to take anything. In check there's no standing pat,
+     *      every evasion gets searched and no moves means mate.
+     *
+     * https://www.chessprogramming.org/Quiescence_Search
+     */
+    fn quiescence(&mut self, state: &mut ChessState, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        if self.visit_node() {
+            return 0;
+        }
+        if ply > 0 && is_draw(state) {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return evaluate(state);
+        }
+
+        let is_in_check = state.check_info.is_check();
+        let mut moves = if is_in_check {
+            let evasions = generate_legal_moves(state);
+            if evasions.is_empty() {
+                return -(MATE_SCORE - ply as i32);
+            }
+            evasions
+        } else {
+            let stand_pat = evaluate(state);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            generate_legal_captures(state)
+        };
+        order_moves(&mut moves, &state.board, None);
+
+        for mov in moves {
+            let undo = state.make_move(mov);
+            let score = -self.quiescence(state, ply + 1, -beta, -alpha);
+            state.unmake_move(undo);
+            if self.is_stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+
+    /**
+     * Counts a node and looks at the limits every so often, true once the search has to stop.
+     */
+    fn visit_node(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(LIMIT_CHECK_INTERVAL) {
+            self.check_limits();
+        }
+        self.is_stopped
+    }
+
+    fn check_limits(&mut self) {
+        let is_out_of_time = matches!(self.limits.movetime, Some(movetime) if self.started.elapsed() >= movetime);
+        let is_out_of_nodes = matches!(self.limits.nodes, Some(nodes) if self.nodes >= nodes);
+        let is_told_to_stop = matches!(&self.limits.stop, Some(stop) if stop.load(Ordering::Relaxed));
+        self.is_stopped = is_out_of_time || is_out_of_nodes || is_told_to_stop;
+    }
+}
+
+// a repetition is as good as a draw, the side ahead will avoid it.
+fn is_draw(state: &ChessState) -> bool {
+    count_repetitions(state) >= 2 || is_insufficient_material(&state.board)
+}
+
+/**
+ * The previous best move first, then captures with the most valuable victim and the least
+ *      valuable attacker first (MVV-LVA), then everything else.
+ */
+fn order_moves(moves: &mut [Move], board: &[BoardPiece; 64], pv_move: Option<Move>) {
+    moves.sort_by_cached_key(|mov| {
+        if Some(*mov) == pv_move {
+            return i32::MIN;
+        }
+        let victim = match mov.move_type {
+            MoveType::EnPassant => piece_value(ChessPieces::Pawns),
+            _ => piece_value(board[mov.target_square as usize].piece_type),
+        };
+        let promotion = mov.promotion.map_or(0, piece_value);
+        let attacker = piece_value(board[mov.start_square as usize].piece_type);
+        if victim == 0 && promotion == 0 {
+            return 0;
+        }
+        -(victim * 10 + promotion * 10 - attacker / 10)
+    });
+}
+
+fn piece_value(piece_type: ChessPieces) -> i32 {
+    match piece_type {
+        ChessPieces::Pawns => 100,
+        ChessPieces::Knights => 320,
+        ChessPieces::Bishops => 330,
+        ChessPieces::Rooks => 500,
+        ChessPieces::Queens => 900,
+        ChessPieces::Kings | ChessPieces::Empty => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_state::algebraic_notation_encoder;
+    use crate::helpers::fen::encode::load_fen_state;
+
+    fn search_fen(fen: &str, depth: u32) -> SearchResult {
+        let mut state = load_fen_state(fen.to_string()).unwrap();
+        let before = state.clone();
+        let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+        let result = search(&mut state, limits, &mut TranspositionTable::new(1));
+        assert_eq!(state, before, "search has to leave the position as it found it");
+        result
+    }
+
+    fn best_move(result: &SearchResult) -> String {
+        algebraic_notation_encoder(&result.best_move.unwrap())
+    }
+
+    #[test]
+    fn finds_mates() {
+        let back_rank = search_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 4);
+        assert_eq!(best_move(&back_rank), "a1a8");
+        assert_eq!(mate_in(back_rank.score), Some(1));
+
+        // Rb7 Kg8 Ra8#
+        let mate_in_two = search_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 4);
+        assert_eq!(mate_in(mate_in_two.score), Some(2));
+
+        let mated = search_fen("6k1/8/8/8/8/8/5PPP/r3r1K1 w - - 0 1", 3);
+        assert!(mated.best_move.is_none());
+    }
+
+    #[test]
+    fn takes_free_material() {
+        let result = search_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 3);
+        assert_eq!(best_move(&result), "d2d5");
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn sees_past_the_horizon() {
+        // d5 is defended, a one ply search without quiescence would grab it with the queen.
+        let result = search_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1);
+        assert_ne!(best_move(&result), "d1d5");
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let mut state = load_fen_state(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        )
+        .unwrap();
+        let limits = SearchLimits { nodes: Some(5_000), ..Default::default() };
+        let result = search(&mut state, limits, &mut TranspositionTable::new(1));
+        assert!(result.best_move.is_some());
+        assert!(result.nodes < 5_000 + LIMIT_CHECK_INTERVAL);
+    }
+}
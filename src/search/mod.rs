@@ -0,0 +1,3 @@
+pub mod alpha_beta;
+pub mod transposition;
+pub mod time_management;
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+// moves left in the game when the GUI doesn't say.
+pub const DEFAULT_MOVES_TO_GO: u64 = 30;
+// kept back from every clock so GUI and pipe lag don't lose on time.
+const MOVE_OVERHEAD_MS: u64 = 50;
+
+/**
+ * A slice of the clock for this move: the time left spread over the moves still to play,
+ *      plus most of the increment, never more than what's left after the overhead.
+ */
+pub fn allocate_time(time_left_ms: u64, increment_ms: u64, moves_to_go: Option<u64>) -> Duration {
+    let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let budget = time_left_ms / moves_to_go + increment_ms * 3 / 4;
+    let available = time_left_ms.saturating_sub(MOVE_OVERHEAD_MS);
+    Duration::from_millis(budget.min(available).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_time_is_spread_over_the_game() {
+        assert_eq!(allocate_time(60_000, 0, None), Duration::from_millis(2_000));
+        assert_eq!(allocate_time(60_000, 1_000, Some(10)), Duration::from_millis(6_750));
+        // nearly flagging, only the time left minus the overhead.
+        assert_eq!(allocate_time(100, 1_000, Some(1)), Duration::from_millis(50));
+        assert_eq!(allocate_time(10, 0, None), Duration::from_millis(1));
+    }
+}
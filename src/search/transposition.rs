@@ -0,0 +1,250 @@
+use std::mem::size_of;
+
+use crate::search::alpha_beta::{MATE_SCORE, MAX_PLY};
+use crate::types::Move;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/**
+ * What a stored score says about the real one, alpha-beta doesn't always get an exact score.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    // failed high, the real score is at least this.
+    Lower,
+    // failed low, the real score is at most this.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableEntry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+    // the search that stored it, older entries get replaced first.
+    pub age: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    // stores that replaced a different position.
+    pub overwrites: u64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / self.probes as f64
+    }
+}
+
+/**
+ * Remembers searched positions by Zobrist key, so transpositions (and the next iteration of
+ *      iterative deepening) can reuse the score and try the best move first.
+ *
+ * The number of slots is a power of two so the key can be masked into an index.
+ * A slot holds one entry, a new entry replaces the old one when:
+ * - it's the same position, or the slot is empty.
+ * - the old entry is from an earlier search.
+ * - the new entry was searched at least as deep.
+ *
+ * https://www.chessprogramming.org/Transposition_Table
+ */
+pub struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+    age: u8,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        TranspositionTable {
+            entries: vec![None; entry_count(size_mb)],
+            age: 0,
+            stats: TableStats::default(),
+        }
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TranspositionTable::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.age = 0;
+        self.stats = TableStats::default();
+    }
+
+    /**
+     * How many entries fit, filled or not.
+     */
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /**
+     * Called before every search, so the entries left over from the last one age.
+     */
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+        self.stats = TableStats::default();
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    /**
+     * Per mille of the table filled by the current search, sampled from the first thousand slots.
+     */
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let filled = sample
+            .iter()
+            .filter(|entry| matches!(entry, Some(entry) if entry.age == self.age))
+            .count();
+        (filled * 1000 / sample.len()) as u32
+    }
+
+    /**
+     * The entry for a position, with any mate score turned back into "mate from this ply".
+     */
+    pub fn probe(&mut self, key: u64, ply: u32) -> Option<TableEntry> {
+        self.stats.probes += 1;
+        let entry = self.entries[self.index(key)].filter(|entry| entry.key == key)?;
+        self.stats.hits += 1;
+        Some(TableEntry {
+            score: score_from_table(entry.score, ply),
+            ..entry
+        })
+    }
+
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+        ply: u32,
+    ) {
+        let index = self.index(key);
+        let depth = depth.min(u8::MAX as u32) as u8;
+        if let Some(old) = self.entries[index] {
+            let is_replaceable = old.key == key || old.age != self.age || depth >= old.depth;
+            if !is_replaceable {
+                return;
+            }
+            if old.key != key {
+                self.stats.overwrites += 1;
+            }
+        }
+
+        // keep the old best move over none, it's still the best guess for this position.
+        let old_best_move = self.entries[index]
+            .filter(|old| old.key == key)
+            .and_then(|old| old.best_move);
+        self.stats.stores += 1;
+        self.entries[index] = Some(TableEntry {
+            key,
+            depth,
+            score: score_to_table(score, ply),
+            bound,
+            best_move: best_move.or(old_best_move),
+            age: self.age,
+        });
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+// the biggest power of two that fits, at least one slot.
+fn entry_count(size_mb: usize) -> usize {
+    let count = (size_mb * 1024 * 1024 / size_of::<Option<TableEntry>>()).max(1);
+    1 << count.ilog2()
+}
+
+/**
+ * Mate scores count plies from the root, but the same position can be reached at any ply.
+ * They're stored as "mate from this node" instead and turned back when probed.
+ */
+fn score_to_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_SCORE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score < -(MATE_SCORE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_SCORE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score < -(MATE_SCORE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_powers_of_two() {
+        for size_mb in [1, 3, 16, 100] {
+            let table = TranspositionTable::new(size_mb);
+            assert!(table.capacity().is_power_of_two());
+            assert!(table.capacity() * size_of::<Option<TableEntry>>() <= size_mb * 1024 * 1024);
+        }
+        assert_eq!(TranspositionTable::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_node() {
+        let mut table = TranspositionTable::new(1);
+        // mated 2 plies below a node at ply 3.
+        table.store(42, 4, -(MATE_SCORE - 5), Bound::Exact, None, 3);
+        assert_eq!(table.probe(42, 3).unwrap().score, -(MATE_SCORE - 5));
+        // the same node reached at ply 7 is mated at ply 9.
+        assert_eq!(table.probe(42, 7).unwrap().score, -(MATE_SCORE - 9));
+
+        table.store(7, 4, 150, Bound::Lower, None, 3);
+        assert_eq!(table.probe(7, 10).unwrap().score, 150);
+    }
+
+    #[test]
+    fn replaces_shallow_and_old_entries() {
+        let mut table = TranspositionTable::new(0);
+        table.store(1, 5, 10, Bound::Exact, None, 0);
+        // a shallower search of another position doesn't push out a deeper one.
+        table.store(2, 3, 20, Bound::Exact, None, 0);
+        assert!(table.probe(2, 0).is_none());
+        assert_eq!(table.probe(1, 0).unwrap().depth, 5);
+
+        table.new_search();
+        table.store(2, 3, 20, Bound::Exact, None, 0);
+        assert_eq!(table.probe(2, 0).unwrap().score, 20);
+        assert!(table.probe(1, 0).is_none());
+        assert_eq!(table.stats().overwrites, 1);
+        assert_eq!(table.stats().hit_rate(), 0.5);
+    }
+}
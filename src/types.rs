@@ -1,5 +1,13 @@
+/**
+ * How many squares a slider can travel from each square in each of the 8 directions,
+ *      from precompute_squares_to_edge.
+ */
+#[doc(hidden)]
 pub type SquaresToEdge = [[i16; 8]; 64];
 
+/**
+ * What's on a square, empty squares are ChessPieces::Empty with PieceColor::None.
+ */
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct BoardPiece {
     pub piece_type: ChessPieces,
@@ -41,17 +49,87 @@ pub enum MoveType {
     Normal, // piece can capture.
 }
 
+/**
+ * A move from one square to another, squares count from a1 = 0 to h8 = 63.
+ */
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Move {
     pub start_square: i16,
     pub target_square: i16,
     pub move_type: MoveType,
+    // the piece a pawn turns into, only set for MoveType::Promotion.
+    pub promotion: Option<ChessPieces>,
 }
 
-#[derive(Debug, Default, Clone)]
+/**
+ * The castling rights still left, not whether castling is possible right now.
+ *
+ * Each right holds the file of the rook it castles with (a = 0), so Chess960 positions
+ *      with the rooks away from the corners work the same as the normal start.
+ * Whichever file the rook starts on, the king ends up on the g or c file and the rook
+ *      next to it on the f or d file.
+ */
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Castle {
-    pub black_queenside: bool,
-    pub black_kingside: bool,
-    pub white_queenside: bool,
-    pub white_kingside: bool,
+    pub black_queenside: Option<usize>,
+    pub black_kingside: Option<usize>,
+    pub white_queenside: Option<usize>,
+    pub white_kingside: Option<usize>,
+}
+
+impl Castle {
+    /**
+     * Every right with the rooks in the corners, like the normal starting position.
+     */
+    pub fn standard() -> Castle {
+        Castle {
+            black_queenside: Some(0),
+            black_kingside: Some(7),
+            white_queenside: Some(0),
+            white_kingside: Some(7),
+        }
+    }
+
+    pub fn rook_file(&self, color: &PieceColor, is_kingside: bool) -> Option<usize> {
+        match (color, is_kingside) {
+            (PieceColor::White, true) => self.white_kingside,
+            (PieceColor::White, false) => self.white_queenside,
+            (PieceColor::Black, true) => self.black_kingside,
+            (PieceColor::Black, false) => self.black_queenside,
+            (PieceColor::None, _) => None,
+        }
+    }
+
+    pub fn has_rights(&self, color: &PieceColor) -> bool {
+        self.rook_file(color, true).is_some() || self.rook_file(color, false).is_some()
+    }
+
+    /**
+     * Drops both rights of a color, for when its king moves.
+     */
+    pub fn clear(&mut self, color: &PieceColor) {
+        match color {
+            PieceColor::White => (self.white_kingside, self.white_queenside) = (None, None),
+            PieceColor::Black => (self.black_kingside, self.black_queenside) = (None, None),
+            PieceColor::None => (),
+        }
+    }
+
+    /**
+     * Drops the right tied to the rook starting on square, if there is one.
+     * Anything moving from or onto it means the rook either moved away or got captured.
+     */
+    pub fn remove_rook(&mut self, square: usize) {
+        let file = Some(square % 8);
+        let rights = match square / 8 {
+            0 => [&mut self.white_kingside, &mut self.white_queenside],
+            7 => [&mut self.black_kingside, &mut self.black_queenside],
+            _ => return,
+        };
+        for right in rights {
+            if *right == file {
+                *right = None;
+            }
+        }
+    }
 }
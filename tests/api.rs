@@ -0,0 +1,83 @@
+//! Drives the library the way another crate would, through the exported API only.
+
+use cirno::perft::perft;
+use cirno::{
+    detect_game_result, generate_legal_moves, detect_check, load_valid_fen_state,
+    move_to_san, san_to_move, save_fen_state, ChessPieces, ChessState, FenError, FenField,
+    GameResult, MoveType, PieceColor, START_POSITION,
+};
+
+fn load(fen: &str) -> ChessState {
+    load_valid_fen_state(fen.to_string()).unwrap()
+}
+
+fn play(state: &mut ChessState, sans: &[&str]) {
+    for san in sans {
+        let mov = san_to_move(state, san).unwrap();
+        state.make_move(mov);
+    }
+}
+
+fn game_result(state: &ChessState) -> GameResult {
+    let moves = generate_legal_moves(state);
+    let is_in_check = detect_check(state, &state.color_to_move);
+    detect_game_result(state, &moves, is_in_check)
+}
+
+#[test]
+fn fen_round_trip_and_errors() {
+    let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let state = load(kiwipete);
+    assert_eq!(save_fen_state(&state).unwrap(), kiwipete);
+    assert_eq!(state.color_to_move, PieceColor::White);
+    assert_eq!(state.board[0].piece_type, ChessPieces::Rooks);
+
+    let err = load_valid_fen_state("8/8/8/8/8/8/8/8 w - - 0 1".to_string()).unwrap_err();
+    assert!(matches!(err, FenError::WrongKingCount { color: PieceColor::White, count: 0 }));
+    let err = load_valid_fen_state("rnbqkbnr/pppppppp w KQkq - 0 1".to_string()).unwrap_err();
+    assert_eq!(err.field(), Some(FenField::Board));
+}
+
+#[test]
+fn make_and_unmake_restore_the_position() {
+    let mut state = load("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let before = state.clone();
+
+    let moves = generate_legal_moves(&state);
+    assert_eq!(moves.len(), 48);
+    assert!(moves.iter().any(|mov| mov.move_type == MoveType::Castle));
+    for mov in moves {
+        let undo = state.make_move(mov);
+        assert_ne!(state.zobrist_key, before.zobrist_key);
+        state.unmake_move(undo);
+        assert_eq!(state, before);
+    }
+
+    assert_eq!(perft(&mut state, 3), 97_862);
+    assert_eq!(state, before);
+}
+
+#[test]
+fn san_in_and_out() {
+    let mut state = load(START_POSITION);
+    play(&mut state, &["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+
+    let mov = san_to_move(&state, "a6").unwrap();
+    assert_eq!(move_to_san(&state, &mov), "a6");
+    assert!(san_to_move(&state, "Ke2").is_err());
+}
+
+#[test]
+fn game_results() {
+    let mut state = load(START_POSITION);
+    assert_eq!(game_result(&state), GameResult::Ongoing);
+    play(&mut state, &["f3", "e5", "g4", "Qh4#"]);
+    assert_eq!(game_result(&state), GameResult::Checkmate);
+
+    let stalemate = load("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    assert_eq!(game_result(&stalemate), GameResult::Stalemate);
+
+    let mut repeating = load(START_POSITION);
+    play(&mut repeating, &["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"]);
+    assert_eq!(game_result(&repeating), GameResult::ThreefoldRepetition);
+}
@@ -0,0 +1,46 @@
+//! Runs `cirno epd` over a tiny test suite, once under the pass-rate threshold and once over it.
+
+use std::fs;
+use std::process::{Command, Output};
+
+const SUITE: &str = r#"# four positions the engine gets and one it never will
+6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - bm Ra8#; dm 1; id "back rank";
+6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - dm 1; id "just mate";
+4k3/8/8/3q4/8/8/8/3RK3 w - - bm Rxd5; id "free queen";
+4k3/8/8/8/8/8/3q4/3RK3 w - - am Kf1; id "take it";
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm Na3; id "never";
+"#;
+
+fn run_suite(name: &str, threshold: &str) -> (Vec<String>, Output) {
+    let path = std::env::temp_dir().join(format!("cirno-epd-test-{}-{}.epd", name, std::process::id()));
+    fs::write(&path, SUITE).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .arg("epd")
+        .arg(&path)
+        .args(["--movetime", "100", "--threshold", threshold])
+        .output()
+        .expect("failed to run cirno");
+    fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    (stdout.lines().map(str::to_string).collect(), output)
+}
+
+#[test]
+fn reports_every_position_and_the_pass_rate() {
+    let (lines, output) = run_suite("pass", "70");
+    assert!(lines[0].starts_with("back rank: pass, played Ra8# (bm Ra8#, dm 1)"), "{}", lines[0]);
+    assert!(lines[1].starts_with("just mate: pass, played Ra8# (dm 1)"), "{}", lines[1]);
+    assert!(lines[2].starts_with("free queen: pass, played Rxd5 (bm Rxd5)"), "{}", lines[2]);
+    assert!(lines[3].starts_with("take it: pass, played Kxd2 (am Kf1)"), "{}", lines[3]);
+    assert!(lines[4].starts_with("never: fail"), "{}", lines[4]);
+    assert_eq!(lines[6..], ["Passed: 4/5 (80.0%)", "Errors: 0"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn fails_below_the_threshold() {
+    let (lines, output) = run_suite("fail", "90");
+    assert_eq!(lines.last().unwrap(), "Below the 90% threshold!");
+    assert!(!output.status.success());
+}
@@ -0,0 +1,110 @@
+//! Runs `cirno perft` against the published node counts.
+//! https://www.chessprogramming.org/Perft_Results
+
+use std::process::Command;
+
+const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str =
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+// https://www.chessprogramming.org/Chess960_Perft_Results, in Shredder-FEN.
+const CHESS960_POSITIONS: [(&str, [u64; 4]); 6] = [
+    ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189, 326672]),
+    ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002, 667366]),
+    // the king and rook swap squares castling kingside.
+    ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471, 273318]),
+    ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", [22, 593, 13440, 382958]),
+    ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", [28, 1120, 31058, 1171749]),
+    ("qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9", [29, 899, 26578, 824055]),
+];
+
+/**
+ * The node count of every depth, as printed by `cirno perft`.
+ */
+fn run_perft(fen: &str, depth: u32) -> Vec<u64> {
+    let output = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .args(["perft", &depth.to_string(), "--fen", fen])
+        .output()
+        .expect("failed to run cirno");
+    assert!(output.status.success(), "cirno perft failed for {}", fen);
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            // Depth 3: 8902 nodes (...)
+            let nodes = line.split_whitespace().nth(2).unwrap();
+            nodes.parse().unwrap()
+        })
+        .collect()
+}
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    assert_eq!(run_perft(fen, expected.len() as u32), expected, "{}", fen);
+}
+
+#[test]
+fn start_position() {
+    assert_perft(START_POSITION, &[20, 400, 8902, 197281]);
+}
+
+#[test]
+fn kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2039, 97862]);
+}
+
+#[test]
+fn position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+}
+
+#[test]
+fn position_4() {
+    assert_perft(POSITION_4, &[6, 264, 9467, 422333]);
+}
+
+#[test]
+fn position_4_mirrored() {
+    assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467, 422333]);
+}
+
+#[test]
+fn position_5() {
+    assert_perft(POSITION_5, &[44, 1486, 62379]);
+}
+
+#[test]
+fn position_6() {
+    assert_perft(POSITION_6, &[46, 2079, 89890]);
+}
+
+#[test]
+fn chess960() {
+    for (fen, expected) in CHESS960_POSITIONS {
+        assert_perft(fen, &expected);
+    }
+}
+
+#[test]
+fn divide_adds_up_to_perft() {
+    let output = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .args(["divide", "3", "--fen", KIWIPETE])
+        .output()
+        .expect("failed to run cirno");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let root_moves: Vec<u64> = stdout
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.split(": ").nth(1).unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(root_moves.len(), 48);
+    assert_eq!(root_moves.iter().sum::<u64>(), 97862);
+    assert!(stdout.contains("Nodes: 97862"));
+}
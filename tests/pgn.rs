@@ -0,0 +1,43 @@
+//! Runs `cirno pgn` over a small multi-game file.
+
+use std::fs;
+use std::process::Command;
+
+const GAMES: &str = r#"[Event "Opera Game"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move already.} 4. dxe5 Bxf3 5. Qxf3 dxe5
+6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 (8. Qxb7 Qb4+ 9. Qxb4 Bxb4+) 8... c6 9. Bg5 b5 10. Nxb5 cxb5
+11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8
+17. Rd8# 1-0
+
+[Event "Broken"]
+[White "?"]
+[Black "?"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf8# 1-0
+"#;
+
+#[test]
+fn replays_games_and_reports_the_broken_one() {
+    let path = std::env::temp_dir().join(format!("cirno-pgn-test-{}.pgn", std::process::id()));
+    fs::write(&path, GAMES).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .arg("pgn")
+        .arg(&path)
+        .output()
+        .expect("failed to run cirno");
+    fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "Game 1: Paul Morphy - Duke Karl / Count Isouard, 33 plies, 1-0");
+    assert_eq!(lines[1], "Game 2: Error! Line 18: Qxf8# isn't a legal move.");
+    assert_eq!(lines[3..], ["Games: 2", "Errors: 1"]);
+    assert!(!output.status.success());
+}
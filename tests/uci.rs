@@ -0,0 +1,87 @@
+//! Pipes scripted UCI sessions into `cirno uci`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_uci(script: &str) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .arg("uci")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run cirno");
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+fn best_move(lines: &[String]) -> &str {
+    let line = lines.iter().rev().find(|line| line.starts_with("bestmove")).expect("no bestmove");
+    line.split_whitespace().nth(1).unwrap()
+}
+
+#[test]
+fn handshake() {
+    let lines = run_uci("uci\nisready\nquit\n");
+    assert!(lines[0].starts_with("id name Cirno"));
+    assert!(lines.iter().any(|line| line.starts_with("option name Hash type spin")));
+    assert!(lines.iter().any(|line| line == "option name UCI_Chess960 type check default false"));
+    let uciok = lines.iter().position(|line| line == "uciok").unwrap();
+    assert_eq!(lines[uciok + 1], "readyok");
+}
+
+#[test]
+fn searches_from_a_position_with_moves() {
+    let lines = run_uci("ucinewgame\nposition startpos moves e2e4 e7e5 g1f3\ngo depth 3\n");
+    for depth in 1..=3 {
+        let prefix = format!("info depth {} score cp ", depth);
+        assert!(lines.iter().any(|line| line.starts_with(&prefix)), "{:?}", lines);
+    }
+    assert!(lines.iter().all(|line| !line.starts_with("info depth 4")));
+    assert!(lines.iter().filter(|line| line.starts_with("info")).all(|line| line.contains(" hashfull ")));
+    // black to move after 1. e4 e5 2. Nf3.
+    let reply = best_move(&lines);
+    assert!(reply.starts_with(|file| ('a'..='h').contains(&file)));
+    assert!(matches!(&reply[1..2], "8" | "7" | "6" | "5"), "{}", reply);
+}
+
+#[test]
+fn finds_mate_from_a_fen() {
+    let lines = run_uci("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 4\n");
+    assert!(lines.iter().any(|line| line.contains("score mate 1")));
+    assert_eq!(best_move(&lines), "a1a8");
+}
+
+#[test]
+fn chess960_castles_king_takes_rook() {
+    // boxed in on g1, castling with the h1 rook (which leaves the king where it is) is the only move.
+    let fen = "k4r2/2b5/8/8/8/4n2p/7P/6KR w H - 0 1";
+    let lines = run_uci(&format!("setoption name UCI_Chess960 value true\nposition fen {}\ngo depth 2\n", fen));
+    assert_eq!(best_move(&lines), "g1h1");
+
+    // and it's read back the same way.
+    let lines = run_uci(&format!("setoption name UCI_Chess960 value true\nposition fen {} moves g1h1\ngo depth 1\n", fen));
+    assert!(lines.iter().all(|line| !line.starts_with("info string")), "{:?}", lines);
+    assert_ne!(best_move(&lines), "0000");
+}
+
+#[test]
+fn clocks_and_stop() {
+    let lines = run_uci("position startpos\ngo wtime 2000 btime 2000 winc 0 binc 0\n");
+    assert_ne!(best_move(&lines), "0000");
+
+    // stop right away still answers, and only once.
+    let lines = run_uci("setoption name Hash value 4\nposition startpos\ngo infinite\nstop\nisready\n");
+    assert_eq!(lines.iter().filter(|line| line.starts_with("bestmove")).count(), 1);
+    assert_eq!(lines.last().unwrap(), "readyok");
+}
+
+#[test]
+fn bad_input_is_reported_not_fatal() {
+    let lines = run_uci("position startpos moves e2e5\nposition fen nonsense\nfoo\ngo depth 1\n");
+    assert_eq!(lines.iter().filter(|line| line.starts_with("info string")).count(), 3);
+    // the position stays the start position.
+    assert!(best_move(&lines).ends_with(['3', '4']));
+}
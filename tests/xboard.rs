@@ -0,0 +1,78 @@
+//! Pipes scripted CECP sessions into `cirno xboard`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_xboard(script: &str) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cirno"))
+        .arg("xboard")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run cirno");
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+fn engine_moves(lines: &[String]) -> Vec<&str> {
+    lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("move "))
+        .collect()
+}
+
+#[test]
+fn feature_negotiation() {
+    let lines = run_xboard("xboard\nprotover 2\nping 7\nquit\n");
+    assert!(lines[0].starts_with("feature myname=\"Cirno"));
+    assert!(lines[0].contains("setboard=1") && lines[0].contains("usermove=1"));
+    assert!(lines[0].ends_with("done=1"));
+    assert_eq!(lines[1], "pong 7");
+}
+
+#[test]
+fn answers_user_moves_and_thinks_when_posting() {
+    let lines = run_xboard("new\npost\nsd 3\nusermove e2e4\n");
+    let moves = engine_moves(&lines);
+    assert_eq!(moves.len(), 1);
+    // black answers 1. e4.
+    assert!(matches!(&moves[0][1..2], "8" | "7" | "6" | "5"), "{:?}", lines);
+    // ply score time nodes pv
+    let thinking: Vec<&String> = lines.iter().filter(|line| !line.starts_with("move")).collect();
+    assert_eq!(thinking.len(), 3);
+    assert!(thinking[2].starts_with("3 "));
+}
+
+#[test]
+fn force_mode_and_go() {
+    // in force mode moves are only recorded, go makes the engine play white here.
+    let lines = run_xboard("new\nforce\nusermove e2e4\nusermove e7e5\nnopost\nsd 2\ngo\n");
+    let moves = engine_moves(&lines);
+    assert_eq!(moves.len(), 1);
+    assert!(matches!(&moves[0][1..2], "1" | "2" | "3" | "4"), "{:?}", lines);
+}
+
+#[test]
+fn reports_results_and_errors() {
+    let lines = run_xboard("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\nsd 3\ngo\n");
+    assert_eq!(engine_moves(&lines), ["a1a8"]);
+    assert_eq!(lines.last().unwrap(), "1-0 {White mates}");
+
+    let lines = run_xboard("new\nforce\nusermove e2e5\nfoo\nsetboard 8/8/8/8/8/8/8/8 w - - 0 1\n");
+    assert_eq!(lines[0], "Illegal move: e2e5");
+    assert_eq!(lines[1], "Error (unknown command): foo");
+    assert!(lines[2].starts_with("tellusererror Illegal position"));
+}
+
+#[test]
+fn undo_and_remove() {
+    // e2e4 is only legal again once both moves are taken back.
+    let lines = run_xboard("new\nforce\nusermove e2e4\nusermove e7e5\nremove\nusermove e2e4\n");
+    assert!(lines.is_empty(), "{:?}", lines);
+
+    let lines = run_xboard("new\nforce\nusermove e2e4\nundo\nusermove e2e4\nusermove e2e4\n");
+    assert_eq!(lines, ["Illegal move: e2e4"]);
+}